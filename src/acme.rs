@@ -0,0 +1,339 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Christian Klauser
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::collections::HashMap;
+use std::thread;
+use std::time::Duration;
+
+use chrono::{self, DateTime, Local};
+
+pub mod http_transport;
+pub mod mock_transport;
+
+/// A domain's ACME order, mid-flight: the URLs the ACME directory gave us for polling its status
+/// and for finalizing it once every authorization is `Valid`, plus the authorizations still to be
+/// walked (see [AcmeTransport::new_order]).
+#[derive(Debug, Clone)]
+pub struct OrderHandle {
+    pub order_url: String,
+    pub finalize_url: String,
+    pub authorization_urls: Vec<String>,
+}
+
+/// Status of one of an order's authorizations (RFC 8555 §7.1.4), trimmed down to the values this
+/// companion actually branches on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthorizationStatus {
+    Pending,
+    Valid,
+    Invalid,
+}
+
+/// One domain-validation challenge offered by an authorization. Only the `http-01` kind is ever
+/// attempted; others are ignored (see [provision]).
+#[derive(Debug, Clone)]
+pub struct Challenge {
+    pub kind: ChallengeKind,
+    pub url: String,
+    pub token: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChallengeKind {
+    Http01,
+    Other,
+}
+
+#[derive(Debug, Clone)]
+pub struct Authorization {
+    pub status: AuthorizationStatus,
+    pub challenges: Vec<Challenge>,
+}
+
+/// Status of an order as a whole (RFC 8555 §7.1.6), trimmed down the same way as
+/// [AuthorizationStatus].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderStatus {
+    Pending,
+    Ready,
+    Valid,
+    Invalid,
+}
+
+/// A provisioned certificate for a single domain, along with when it stops being trustworthy.
+#[derive(Debug, Clone)]
+pub struct Certificate {
+    pub domain: String,
+    pub cert_pem: String,
+    pub key_pem: String,
+    pub expires_at: DateTime<Local>,
+}
+
+impl Certificate {
+    /// Whether this certificate should be renewed now, because it expires within `within_days`.
+    pub fn needs_renewal(&self, within_days: u32) -> bool {
+        self.expires_at - chrono::Duration::days(within_days as i64) <= Local::now()
+    }
+}
+
+quick_error! {
+    #[derive(Debug)]
+    pub enum AcmeError {
+        /// This checkout has no HTTP client or JOSE/JWS-signing dependency to actually speak ACME
+        /// with; `msg` names the operation that would have needed one. See
+        /// [http_transport::HttpAcmeTransport] for where a real implementation would plug in.
+        Unsupported(msg: String) {
+            description("ACME operation requires a dependency this build doesn't have.")
+            display(me) -> ("{} ({})", me.description(), msg)
+        }
+        NoHttp01Challenge(domain: String) {
+            description("Authorization didn't offer an http-01 challenge.")
+            display(me) -> ("{} domain: {}", me.description(), domain)
+        }
+        AuthorizationFailed(domain: String) {
+            description("Domain authorization was rejected by the ACME server.")
+            display(me) -> ("{} domain: {}", me.description(), domain)
+        }
+        OrderFailed(domain: String) {
+            description("Certificate order was rejected by the ACME server.")
+            display(me) -> ("{} domain: {}", me.description(), domain)
+        }
+        Other(err: Box<::std::error::Error + Send + Sync>) {
+            description("ACME error.")
+            display(me) -> ("{} Error: {}", me.description(), err)
+        }
+    }
+}
+
+/// Abstracts over the ACME directory this companion talks to: placing orders, fetching
+/// authorizations, responding to challenges, polling status and finalizing with a CSR. Lets
+/// [provision]'s orchestration be exercised against [mock_transport::MockTransport] without a
+/// real ACME server (see [http_transport::HttpAcmeTransport] for the production implementation).
+pub trait AcmeTransport {
+    /// Places a new order for `domain`, returning its authorizations to walk.
+    fn new_order(&mut self, domain: &str) -> Result<OrderHandle, AcmeError>;
+
+    fn authorization(&mut self, url: &str) -> Result<Authorization, AcmeError>;
+
+    /// Computes the key authorization for `token`, binding it to this account's key so the ACME
+    /// server can verify we control it (RFC 8555 §8.1).
+    fn key_authorization(&mut self, token: &str) -> Result<String, AcmeError>;
+
+    /// Tells the ACME server we're ready for it to fetch the challenge at `challenge_url`.
+    fn respond_to_challenge(&mut self, challenge_url: &str) -> Result<(), AcmeError>;
+
+    fn poll_order(&mut self, order_url: &str) -> Result<OrderStatus, AcmeError>;
+
+    /// Finalizes a `Ready` order by submitting a CSR for `domain`, returning the issued
+    /// certificate once the server has signed it.
+    fn finalize(&mut self, finalize_url: &str, domain: &str) -> Result<Certificate, AcmeError>;
+}
+
+/// Exposes ACME http-01 key authorizations at `/.well-known/acme-challenge/<token>` for the ACME
+/// server to fetch. The companion owns this rather than handing challenges to the publisher
+/// backend, since nothing about answering a challenge is Redis-specific.
+pub trait ChallengeResponder {
+    fn publish_challenge(&mut self, token: &str, key_authorization: &str);
+    fn remove_challenge(&mut self, token: &str);
+}
+
+/// [ChallengeResponder] that just keeps key authorizations in memory, keyed by token. A real
+/// deployment would serve this map over HTTP at `/.well-known/acme-challenge/<token>`; wiring up
+/// that listener needs an HTTP server dependency this checkout doesn't have (see
+/// `AcmeError::Unsupported`), so for now this only tracks which tokens are currently "live".
+#[derive(Default)]
+pub struct InMemoryChallengeResponder {
+    pub live_tokens: HashMap<String, String>,
+}
+
+impl ChallengeResponder for InMemoryChallengeResponder {
+    fn publish_challenge(&mut self, token: &str, key_authorization: &str) {
+        self.live_tokens.insert(token.to_owned(), key_authorization.to_owned());
+    }
+
+    fn remove_challenge(&mut self, token: &str) {
+        self.live_tokens.remove(token);
+    }
+}
+
+/// How long to wait between polls while an authorization or order is still `Pending`.
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// Give up on a stuck authorization or order after this many polls, rather than looping forever.
+const MAX_POLLS: u32 = 30;
+
+/// Orders, validates (via http-01) and finalizes a certificate for `domain`, following RFC 8555:
+/// place the order, walk *every* authorization it returns (skipping any already `Valid`), answer
+/// each one's `http-01` challenge, poll until the order is `Ready`, then finalize with a CSR.
+pub fn provision(domain: &str,
+                 transport: &mut AcmeTransport,
+                 responder: &mut ChallengeResponder)
+                 -> Result<Certificate, AcmeError> {
+    let order = try!(transport.new_order(domain));
+
+    for authorization_url in &order.authorization_urls {
+        let authorization = try!(transport.authorization(authorization_url));
+        if authorization.status == AuthorizationStatus::Valid {
+            continue;
+        }
+
+        let challenge = try!(authorization.challenges
+            .iter()
+            .find(|c| c.kind == ChallengeKind::Http01)
+            .ok_or_else(|| AcmeError::NoHttp01Challenge(domain.to_owned())));
+
+        let key_authorization = try!(transport.key_authorization(&challenge.token));
+        responder.publish_challenge(&challenge.token, &key_authorization);
+        try!(transport.respond_to_challenge(&challenge.url));
+
+        let mut polls = 0;
+        loop {
+            let authorization = try!(transport.authorization(authorization_url));
+            match authorization.status {
+                AuthorizationStatus::Valid => break,
+                AuthorizationStatus::Invalid => {
+                    responder.remove_challenge(&challenge.token);
+                    return Err(AcmeError::AuthorizationFailed(domain.to_owned()));
+                }
+                AuthorizationStatus::Pending if polls < MAX_POLLS => {
+                    polls += 1;
+                    thread::sleep(POLL_INTERVAL);
+                }
+                AuthorizationStatus::Pending => {
+                    responder.remove_challenge(&challenge.token);
+                    return Err(AcmeError::AuthorizationFailed(domain.to_owned()));
+                }
+            }
+        }
+
+        responder.remove_challenge(&challenge.token);
+    }
+
+    let mut polls = 0;
+    loop {
+        match try!(transport.poll_order(&order.order_url)) {
+            OrderStatus::Ready => break,
+            OrderStatus::Valid => break,
+            OrderStatus::Invalid => return Err(AcmeError::OrderFailed(domain.to_owned())),
+            OrderStatus::Pending if polls < MAX_POLLS => {
+                polls += 1;
+                thread::sleep(POLL_INTERVAL);
+            }
+            OrderStatus::Pending => return Err(AcmeError::OrderFailed(domain.to_owned())),
+        }
+    }
+
+    transport.finalize(&order.finalize_url, domain)
+}
+
+/// Provisions and renews certificates for domains discovered during a refresh cycle, caching each
+/// issued certificate so we don't re-order one that isn't actually close to expiring yet.
+pub struct AcmeManager {
+    transport: Box<AcmeTransport>,
+    responder: Box<ChallengeResponder>,
+    renew_within_days: u32,
+    certificates: HashMap<String, Certificate>,
+}
+
+impl AcmeManager {
+    pub fn new(transport: Box<AcmeTransport>,
+              responder: Box<ChallengeResponder>,
+              renew_within_days: u32)
+              -> AcmeManager {
+        AcmeManager {
+            transport: transport,
+            responder: responder,
+            renew_within_days: renew_within_days,
+            certificates: HashMap::new(),
+        }
+    }
+
+    /// Provisions a certificate for `domain` if we don't have one yet, or renews it if the one we
+    /// have is within `renew_within_days` of expiring. Does nothing (and returns the cached
+    /// certificate) otherwise.
+    pub fn ensure_certificate(&mut self, domain: &str) -> Result<&Certificate, AcmeError> {
+        let needs_provisioning = match self.certificates.get(domain) {
+            Some(cert) => cert.needs_renewal(self.renew_within_days),
+            None => true,
+        };
+
+        if needs_provisioning {
+            let cert = try!(provision(domain, &mut *self.transport, &mut *self.responder));
+            self.certificates.insert(domain.to_owned(), cert);
+        }
+
+        Ok(self.certificates.get(domain).expect("just provisioned or already present"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::mock_transport::MockTransport;
+
+    #[test]
+    fn provision_skips_already_valid_authorizations() {
+        let mut transport = MockTransport::new_valid("example.org");
+        let mut responder = InMemoryChallengeResponder::default();
+
+        let cert = provision("example.org", &mut transport, &mut responder).unwrap();
+
+        assert_eq!(cert.domain, "example.org");
+        assert_eq!(responder.live_tokens.len(), 0, "no challenge should have been left exposed");
+    }
+
+    #[test]
+    fn provision_answers_pending_http01_challenge() {
+        let mut transport = MockTransport::new_pending("example.org");
+        let mut responder = InMemoryChallengeResponder::default();
+
+        let cert = provision("example.org", &mut transport, &mut responder).unwrap();
+
+        assert_eq!(cert.domain, "example.org");
+        assert!(transport.challenge_was_answered, "the http-01 challenge should have been responded to");
+        assert_eq!(responder.live_tokens.len(), 0, "challenge should be cleaned up after validation");
+    }
+
+    #[test]
+    fn provision_fails_without_http01_challenge() {
+        let mut transport = MockTransport::new_pending_without_http01("example.org");
+        let mut responder = InMemoryChallengeResponder::default();
+
+        let result = provision("example.org", &mut transport, &mut responder);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn ensure_certificate_reuses_cached_certificate() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let transport = Rc::new(RefCell::new(MockTransport::new_valid("example.org")));
+        let responder = Box::new(InMemoryChallengeResponder::default());
+        let mut manager = AcmeManager::new(Box::new(transport.clone()), responder, 30);
+
+        manager.ensure_certificate("example.org").unwrap();
+        manager.ensure_certificate("example.org").unwrap();
+
+        assert_eq!(transport.borrow().orders_placed, 1, "second call shouldn't re-order");
+    }
+}
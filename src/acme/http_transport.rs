@@ -0,0 +1,76 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Christian Klauser
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use url::Url;
+
+use super::{AcmeTransport, AcmeError, OrderHandle, Authorization, OrderStatus, Certificate};
+
+/// [AcmeTransport] that would talk to a real ACME directory (e.g. Let's Encrypt) over HTTPS,
+/// signing every request as a JWS with the configured account key (RFC 8555).
+///
+/// This checkout doesn't depend on an HTTP client or a JOSE/JWS-signing crate (see `extern crate`
+/// declarations in `lib.rs`), so every method here honestly reports
+/// [AcmeError::Unsupported] instead of pretending to talk to a server. The orchestration in
+/// [super::provision] is otherwise complete and already exercised against
+/// [super::mock_transport::MockTransport]; once an HTTP client and crypto dependency are added to
+/// the workspace, only this struct needs to grow real bodies.
+pub struct HttpAcmeTransport {
+    directory_url: Url,
+    account_key_path: String,
+    contact_email: String,
+}
+
+impl HttpAcmeTransport {
+    pub fn new(directory_url: Url, account_key_path: String, contact_email: String) -> HttpAcmeTransport {
+        HttpAcmeTransport {
+            directory_url: directory_url,
+            account_key_path: account_key_path,
+            contact_email: contact_email,
+        }
+    }
+}
+
+impl AcmeTransport for HttpAcmeTransport {
+    fn new_order(&mut self, _domain: &str) -> Result<OrderHandle, AcmeError> {
+        Err(AcmeError::Unsupported(format!("POST {}/new-order", self.directory_url)))
+    }
+
+    fn authorization(&mut self, url: &str) -> Result<Authorization, AcmeError> {
+        Err(AcmeError::Unsupported(format!("GET {}", url)))
+    }
+
+    fn key_authorization(&mut self, _token: &str) -> Result<String, AcmeError> {
+        Err(AcmeError::Unsupported(format!("JWK thumbprint of account key at {}", self.account_key_path)))
+    }
+
+    fn respond_to_challenge(&mut self, url: &str) -> Result<(), AcmeError> {
+        Err(AcmeError::Unsupported(format!("POST {} (account contact: {})", url, self.contact_email)))
+    }
+
+    fn poll_order(&mut self, url: &str) -> Result<OrderStatus, AcmeError> {
+        Err(AcmeError::Unsupported(format!("GET {}", url)))
+    }
+
+    fn finalize(&mut self, url: &str, _domain: &str) -> Result<Certificate, AcmeError> {
+        Err(AcmeError::Unsupported(format!("POST {} (CSR generation)", url)))
+    }
+}
@@ -0,0 +1,252 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Christian Klauser
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use domain_spec::{Host, HostPort};
+
+/// A loaded certificate/key pair, ready to be served for whichever domain pattern it was
+/// [CertStore::insert]ed under.
+#[derive(Debug, Clone)]
+pub struct CertifiedKey {
+    pub cert_pem: String,
+    pub key_pem: String,
+}
+
+/// A domain a [CertifiedKey] was registered for: either an exact match, or a single-label
+/// wildcard (`*.example.com`, matching `foo.example.com` but not `example.com` or
+/// `a.foo.example.com`). Both forms carry an optional port (see [HostPort]): `example.com` and
+/// `example.com:8080` are distinct targets, so a pattern registered for one never matches a
+/// query for the other.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum DomainPattern {
+    Exact(HostPort),
+    Wildcard(HostPort),
+}
+
+impl DomainPattern {
+    fn parse(domain_pattern: &str) -> DomainPattern {
+        if domain_pattern.starts_with("*.") {
+            DomainPattern::Wildcard(HostPort::parse(&domain_pattern[2..]))
+        } else {
+            DomainPattern::Exact(HostPort::parse(domain_pattern))
+        }
+    }
+
+    fn matches(&self, target: &HostPort) -> bool {
+        match *self {
+            DomainPattern::Exact(ref exact) => exact == target,
+            DomainPattern::Wildcard(ref suffix) => {
+                if suffix.port != target.port {
+                    return false;
+                }
+                let (suffix_name, host_name) = match (&suffix.host, &target.host) {
+                    (&Host::Dns(ref suffix_name), &Host::Dns(ref host_name)) => (suffix_name, host_name),
+                    // Wildcarding an IP literal doesn't mean anything; nothing matches it.
+                    _ => return false,
+                };
+                match host_name.len().checked_sub(suffix_name.len() + 1) {
+                    Some(label_len) if label_len > 0 => {
+                        host_name.ends_with(suffix_name.as_str()) &&
+                        host_name.as_bytes()[label_len] == b'.' &&
+                        !host_name[..label_len].contains('.')
+                    }
+                    _ => false,
+                }
+            }
+        }
+    }
+
+    /// How specific a match via this pattern is, used to rank competing matches: an exact match
+    /// always beats a wildcard, a port-scoped pattern beats a port-agnostic one for the same
+    /// host, and among patterns of the same kind and port-scoping, the longer (more specific)
+    /// host wins.
+    fn specificity(&self) -> (u8, u8, usize) {
+        let (exactness, host_port) = match *self {
+            DomainPattern::Exact(ref hp) => (1, hp),
+            DomainPattern::Wildcard(ref hp) => (0, hp),
+        };
+        let port_scoped = if host_port.port.is_some() { 1 } else { 0 };
+        let host_len = match host_port.host {
+            Host::Dns(ref name) => name.len(),
+            Host::Ip(ref ip) => ip.to_string().len(),
+        };
+        (exactness, port_scoped, host_len)
+    }
+}
+
+quick_error! {
+    #[derive(Debug)]
+    pub enum CertStoreError {
+        MissingCertificate(domain: String) {
+            description("No certificate available for domain.")
+            display(me) -> ("{} domain: {}", me.description(), domain)
+        }
+        MissingKey(domain: String) {
+            description("Certificate has no matching private key.")
+            display(me) -> ("{} domain: {}", me.description(), domain)
+        }
+        InvalidDomain(domain: String) {
+            description("Domain pattern is not a valid exact or wildcard domain.")
+            display(me) -> ("{} domain: {:?}", me.description(), domain)
+        }
+    }
+}
+
+/// Holds loaded certificates paired with the domain patterns they cover (see [DomainPattern]),
+/// and resolves a host (optionally `host:port`/`ip:port`/`[ipv6]:port`, see [HostPort]) to the
+/// most specific matching one: an exact match beats a wildcard, a port-scoped pattern beats a
+/// port-agnostic one for the same host, and a longer wildcard beats a shorter one.
+#[derive(Default)]
+pub struct CertStore {
+    // Kept sorted by descending specificity, so `resolve` can just return the first match.
+    entries: Vec<(DomainPattern, CertifiedKey)>,
+}
+
+impl CertStore {
+    pub fn new() -> CertStore {
+        CertStore { entries: Vec::new() }
+    }
+
+    /// Validates `domain_pattern` and registers `key` under it, replacing any existing entry for
+    /// the same pattern. Rejects patterns that are neither an exact domain nor a single-label
+    /// wildcard (e.g. `*`, `a.*.example.com`). `domain_pattern` may carry a `:port`/`[ipv6]:port`
+    /// suffix (see [HostPort]); `example.com` and `example.com:8080` are registered as distinct
+    /// targets.
+    pub fn insert(&mut self, domain_pattern: &str, key: CertifiedKey) -> Result<(), CertStoreError> {
+        let rest = if domain_pattern.starts_with("*.") {
+            &domain_pattern[2..]
+        } else {
+            domain_pattern
+        };
+        if rest.is_empty() || rest.contains('*') {
+            return Err(CertStoreError::InvalidDomain(domain_pattern.to_owned()));
+        }
+
+        let pattern = DomainPattern::parse(domain_pattern);
+        self.entries.retain(|&(ref existing, _)| *existing != pattern);
+        self.entries.push((pattern, key));
+        self.entries.sort_by(|a, b| b.0.specificity().cmp(&a.0.specificity()));
+        Ok(())
+    }
+
+    /// Picks the most specific [CertifiedKey] registered for `host` (may itself carry a
+    /// `:port`/`[ipv6]:port` suffix, see [HostPort]), or `None` if nothing matches.
+    pub fn resolve(&self, host: &str) -> Option<&CertifiedKey> {
+        let target = HostPort::parse(host);
+        self.entries
+            .iter()
+            .find(|&&(ref pattern, _)| pattern.matches(&target))
+            .map(|&(_, ref key)| key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CertStore, CertifiedKey};
+
+    fn key(label: &str) -> CertifiedKey {
+        CertifiedKey { cert_pem: format!("cert:{}", label), key_pem: format!("key:{}", label) }
+    }
+
+    #[test]
+    fn exact_match_beats_wildcard() {
+        let mut store = CertStore::new();
+        store.insert("*.example.com", key("wildcard")).unwrap();
+        store.insert("foo.example.com", key("exact")).unwrap();
+
+        let resolved = store.resolve("foo.example.com").unwrap();
+        assert_eq!(resolved.cert_pem, "cert:exact");
+    }
+
+    #[test]
+    fn wildcard_matches_single_label_subdomain() {
+        let mut store = CertStore::new();
+        store.insert("*.example.com", key("wildcard")).unwrap();
+
+        assert!(store.resolve("foo.example.com").is_some());
+        assert!(store.resolve("example.com").is_none());
+        assert!(store.resolve("a.foo.example.com").is_none());
+    }
+
+    #[test]
+    fn longer_wildcard_beats_shorter_one() {
+        let mut store = CertStore::new();
+        store.insert("*.example.com", key("short")).unwrap();
+        store.insert("*.staging.example.com", key("long")).unwrap();
+
+        let resolved = store.resolve("foo.staging.example.com").unwrap();
+        assert_eq!(resolved.cert_pem, "cert:long");
+    }
+
+    #[test]
+    fn resolve_returns_none_without_a_match() {
+        let store = CertStore::new();
+        assert!(store.resolve("example.com").is_none());
+    }
+
+    #[test]
+    fn insert_rejects_multi_label_wildcards() {
+        let mut store = CertStore::new();
+        assert!(store.insert("*.*.example.com", key("bad")).is_err());
+    }
+
+    #[test]
+    fn host_without_a_port_does_not_match_the_same_host_with_one() {
+        let mut store = CertStore::new();
+        store.insert("example.com:8443", key("scoped")).unwrap();
+
+        assert!(store.resolve("example.com").is_none());
+        assert!(store.resolve("example.com:80").is_none());
+        assert_eq!(store.resolve("example.com:8443").unwrap().cert_pem, "cert:scoped");
+    }
+
+    #[test]
+    fn port_scoped_pattern_beats_port_agnostic_one_for_the_same_host() {
+        let mut store = CertStore::new();
+        store.insert("example.com", key("any-port")).unwrap();
+        store.insert("example.com:8443", key("scoped")).unwrap();
+
+        assert_eq!(store.resolve("example.com:8443").unwrap().cert_pem, "cert:scoped");
+        assert_eq!(store.resolve("example.com").unwrap().cert_pem, "cert:any-port");
+    }
+
+    #[test]
+    fn ip_literal_and_bracketed_ipv6_are_port_aware_too() {
+        let mut store = CertStore::new();
+        store.insert("10.0.0.1:8443", key("v4")).unwrap();
+        store.insert("[::1]:8443", key("v6")).unwrap();
+
+        assert!(store.resolve("10.0.0.1").is_none());
+        assert_eq!(store.resolve("10.0.0.1:8443").unwrap().cert_pem, "cert:v4");
+        assert!(store.resolve("[::1]").is_none());
+        assert_eq!(store.resolve("[::1]:8443").unwrap().cert_pem, "cert:v6");
+    }
+
+    #[test]
+    fn wildcard_port_scoping_is_independent_of_the_host_suffix() {
+        let mut store = CertStore::new();
+        store.insert("*.example.com:8443", key("wildcard-scoped")).unwrap();
+
+        assert!(store.resolve("foo.example.com").is_none(),
+                "the wildcard is port-scoped, so a port-agnostic query shouldn't match");
+        assert_eq!(store.resolve("foo.example.com:8443").unwrap().cert_pem, "cert:wildcard-scoped");
+    }
+}
@@ -0,0 +1,317 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Christian Klauser
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::sync::Arc;
+
+use common::{Config, MissingContainerHandling};
+use inspector::Inspect;
+use publisher::CheckBackend;
+
+/// Nagios/Icinga-plugin exit status, in ascending order of "how urgently an operator should
+/// care" (not the same order as `exit_code`, which follows the fixed Monitoring Plugins
+/// convention).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CheckStatus {
+    Ok,
+    Warning,
+    Unknown,
+    Critical,
+}
+
+impl CheckStatus {
+    pub fn exit_code(&self) -> i32 {
+        match *self {
+            CheckStatus::Ok => 0,
+            CheckStatus::Warning => 1,
+            CheckStatus::Critical => 2,
+            CheckStatus::Unknown => 3,
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match *self {
+            CheckStatus::Ok => "OK",
+            CheckStatus::Warning => "WARNING",
+            CheckStatus::Critical => "CRITICAL",
+            CheckStatus::Unknown => "UNKNOWN",
+        }
+    }
+}
+
+/// Runs a single `--check` pass over `containers`: for each one, inspects it to learn its
+/// registered host (same as a normal refresh) and asks `backend` whether that host's
+/// registration is still live and how much longer it has. Returns the worst status across all
+/// containers together with a one-line, Nagios/Icinga-plugin-style summary suitable for printing
+/// to stdout.
+pub fn check(config: &Config,
+             inspector: &mut Inspect,
+             backend: &CheckBackend,
+             containers: &[Arc<String>])
+             -> (CheckStatus, String) {
+    let mut worst = CheckStatus::Ok;
+    let mut details = Vec::with_capacity(containers.len());
+
+    for container in containers {
+        let (status, detail) = check_one(config, inspector, backend, container);
+        if status > worst {
+            worst = status;
+        }
+        details.push(detail);
+    }
+
+    let summary = if details.is_empty() {
+        format!("{}: no containers to check", worst.label())
+    } else {
+        format!("{}: {}", worst.label(), details.join("; "))
+    };
+    (worst, summary)
+}
+
+fn check_one(config: &Config,
+             inspector: &mut Inspect,
+             backend: &CheckBackend,
+             container: &Arc<String>)
+             -> (CheckStatus, String) {
+    let host = match inspector.inspect(container) {
+        Ok(inspection) => inspection.host,
+        Err(e) => {
+            return if config.missing_container == MissingContainerHandling::Report {
+                (CheckStatus::Critical, format!("{}: not found ({})", container, e))
+            } else {
+                (CheckStatus::Ok, format!("{}: not found, ignored", container))
+            };
+        }
+    };
+
+    match backend.check_ttl(&host) {
+        Ok(None) => (CheckStatus::Critical, format!("{}: registration missing", container)),
+        Ok(Some(ttl)) if ttl < 0 => {
+            (CheckStatus::Ok, format!("{}: registered, no expiration", container))
+        }
+        Ok(Some(ttl)) if (ttl as u32) < config.check_crit_seconds => {
+            (CheckStatus::Critical,
+             format!("{}: TTL {}s below critical threshold ({}s)", container, ttl, config.check_crit_seconds))
+        }
+        Ok(Some(ttl)) if (ttl as u32) < config.check_warn_seconds => {
+            (CheckStatus::Warning,
+             format!("{}: TTL {}s below warning threshold ({}s)", container, ttl, config.check_warn_seconds))
+        }
+        Ok(Some(ttl)) => (CheckStatus::Ok, format!("{}: TTL {}s", container, ttl)),
+        Err(e) => (CheckStatus::Unknown, format!("{}: check failed ({})", container, e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use common::{self, Config, MissingContainerHandling};
+    use inspector::mock_inspector::MockInspector;
+    use inspector::{Inspection, HealthStatus};
+    use publisher::mock_publisher::{MockCheckBackend, MockError};
+    use publisher::PublishingError;
+
+    use super::{check, CheckStatus};
+
+    fn inspection(host: &str) -> Inspection {
+        Inspection {
+            envvar_present: true,
+            host: host.to_owned(),
+            specs: Vec::new(),
+            health: HealthStatus::Healthy,
+        }
+    }
+
+    #[test]
+    fn check_ok_when_ttl_above_thresholds() {
+        common::init_log();
+        // #### GIVEN ####
+        let cfg = Config::default();
+        let alpha = Arc::new("alpha".to_owned());
+        let mut inspector = MockInspector::default();
+        inspector.inspect_results.insert(alpha.clone(), Ok(inspection("alpha.host")));
+        let mut backend = MockCheckBackend::default();
+        backend.ttls.insert("alpha.host".to_owned(), Ok(Some(3600)));
+
+        // #### WHEN  ####
+        let (status, summary) = check(&cfg, &mut inspector, &backend, &[alpha]);
+
+        // #### THEN ####
+        assert_eq!(status, CheckStatus::Ok);
+        assert_eq!(status.exit_code(), 0);
+        assert!(summary.starts_with("OK:"), "summary: {}", summary);
+    }
+
+    #[test]
+    fn check_critical_when_registration_missing() {
+        common::init_log();
+        // #### GIVEN ####
+        let cfg = Config::default();
+        let alpha = Arc::new("alpha".to_owned());
+        let mut inspector = MockInspector::default();
+        inspector.inspect_results.insert(alpha.clone(), Ok(inspection("alpha.host")));
+        let backend = MockCheckBackend::default();
+
+        // #### WHEN  ####
+        let (status, summary) = check(&cfg, &mut inspector, &backend, &[alpha]);
+
+        // #### THEN ####
+        assert_eq!(status, CheckStatus::Critical);
+        assert_eq!(status.exit_code(), 2);
+        assert!(summary.contains("registration missing"), "summary: {}", summary);
+    }
+
+    #[test]
+    fn check_warning_when_ttl_below_warn_threshold() {
+        common::init_log();
+        // #### GIVEN ####
+        let mut cfg = Config::default();
+        cfg.check_warn_seconds = 30;
+        let alpha = Arc::new("alpha".to_owned());
+        let mut inspector = MockInspector::default();
+        inspector.inspect_results.insert(alpha.clone(), Ok(inspection("alpha.host")));
+        let mut backend = MockCheckBackend::default();
+        backend.ttls.insert("alpha.host".to_owned(), Ok(Some(5)));
+
+        // #### WHEN  ####
+        let (status, _) = check(&cfg, &mut inspector, &backend, &[alpha]);
+
+        // #### THEN ####
+        assert_eq!(status, CheckStatus::Warning);
+        assert_eq!(status.exit_code(), 1);
+    }
+
+    #[test]
+    fn check_critical_when_ttl_below_crit_threshold() {
+        common::init_log();
+        // #### GIVEN ####
+        let mut cfg = Config::default();
+        cfg.check_warn_seconds = 30;
+        cfg.check_crit_seconds = 10;
+        let alpha = Arc::new("alpha".to_owned());
+        let mut inspector = MockInspector::default();
+        inspector.inspect_results.insert(alpha.clone(), Ok(inspection("alpha.host")));
+        let mut backend = MockCheckBackend::default();
+        backend.ttls.insert("alpha.host".to_owned(), Ok(Some(5)));
+
+        // #### WHEN  ####
+        let (status, _) = check(&cfg, &mut inspector, &backend, &[alpha]);
+
+        // #### THEN ####
+        assert_eq!(status, CheckStatus::Critical);
+    }
+
+    #[test]
+    fn check_crit_threshold_disabled_by_default() {
+        common::init_log();
+        // #### GIVEN ####
+        let cfg = Config::default();
+        assert_eq!(cfg.check_crit_seconds, 0, "default crit threshold must be disabled");
+        let alpha = Arc::new("alpha".to_owned());
+        let mut inspector = MockInspector::default();
+        inspector.inspect_results.insert(alpha.clone(), Ok(inspection("alpha.host")));
+        let mut backend = MockCheckBackend::default();
+        backend.ttls.insert("alpha.host".to_owned(), Ok(Some(0)));
+
+        // #### WHEN  ####
+        // A TTL of 0 would trip a crit threshold of e.g. 1, but 0 (the default) disables it.
+        let (status, _) = check(&cfg, &mut inspector, &backend, &[alpha]);
+
+        // #### THEN ####
+        assert_eq!(status, CheckStatus::Warning, "falls through to the (always-on) warn threshold");
+    }
+
+    #[test]
+    fn check_ok_when_no_expiration() {
+        common::init_log();
+        // #### GIVEN ####
+        let cfg = Config::default();
+        let alpha = Arc::new("alpha".to_owned());
+        let mut inspector = MockInspector::default();
+        inspector.inspect_results.insert(alpha.clone(), Ok(inspection("alpha.host")));
+        let mut backend = MockCheckBackend::default();
+        backend.ttls.insert("alpha.host".to_owned(), Ok(Some(-1)));
+
+        // #### WHEN  ####
+        let (status, summary) = check(&cfg, &mut inspector, &backend, &[alpha]);
+
+        // #### THEN ####
+        assert_eq!(status, CheckStatus::Ok);
+        assert!(summary.contains("no expiration"), "summary: {}", summary);
+    }
+
+    #[test]
+    fn check_ignores_missing_container_by_default() {
+        common::init_log();
+        // #### GIVEN ####
+        let cfg = Config::default();
+        assert_eq!(cfg.missing_container, MissingContainerHandling::Ignore);
+        let alpha = Arc::new("alpha".to_owned());
+        let mut inspector = MockInspector::default();
+        let backend = MockCheckBackend::default();
+
+        // #### WHEN  ####
+        let (status, summary) = check(&cfg, &mut inspector, &backend, &[alpha]);
+
+        // #### THEN ####
+        assert_eq!(status, CheckStatus::Ok);
+        assert!(summary.contains("ignored"), "summary: {}", summary);
+    }
+
+    #[test]
+    fn check_reports_missing_container_when_configured() {
+        common::init_log();
+        // #### GIVEN ####
+        let mut cfg = Config::default();
+        cfg.missing_container = MissingContainerHandling::Report;
+        let alpha = Arc::new("alpha".to_owned());
+        let mut inspector = MockInspector::default();
+        let backend = MockCheckBackend::default();
+
+        // #### WHEN  ####
+        let (status, summary) = check(&cfg, &mut inspector, &backend, &[alpha]);
+
+        // #### THEN ####
+        assert_eq!(status, CheckStatus::Critical);
+        assert!(summary.contains("not found"), "summary: {}", summary);
+    }
+
+    #[test]
+    fn check_unknown_when_backend_errors() {
+        common::init_log();
+        // #### GIVEN ####
+        let cfg = Config::default();
+        let alpha = Arc::new("alpha".to_owned());
+        let mut inspector = MockInspector::default();
+        inspector.inspect_results.insert(alpha.clone(), Ok(inspection("alpha.host")));
+        let mut backend = MockCheckBackend::default();
+        backend.ttls.insert("alpha.host".to_owned(),
+                            Err(Box::new(|| PublishingError::Other(Box::new(MockError)))));
+
+        // #### WHEN  ####
+        let (status, summary) = check(&cfg, &mut inspector, &backend, &[alpha]);
+
+        // #### THEN ####
+        assert_eq!(status, CheckStatus::Unknown);
+        assert!(summary.contains("check failed"), "summary: {}", summary);
+    }
+}
@@ -0,0 +1,209 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Christian Klauser
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::sync::Arc;
+use std::fmt::Display;
+#[cfg(test)]
+use std::sync::{Once, ONCE_INIT};
+
+use url::Url;
+
+use companion::CompanionError;
+
+/// How to treat a container that doesn't define the configured environment variable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MissingEnvVarHandling {
+    /// Explicitly listed containers are an error, enumerated ones are not.
+    Automatic,
+    /// Always treat the missing variable as an error, regardless of how the container was found.
+    Report,
+    /// Never treat the missing variable as an error, regardless of how the container was found.
+    Ignore,
+}
+
+/// How to treat an explicitly listed container that couldn't be found/inspected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MissingContainerHandling {
+    Report,
+    Ignore,
+}
+
+/// Effective, fully resolved configuration for a companion run. Built once from the parsed
+/// command line arguments and then shared read-only (via `Arc`) with the inspector, the
+/// publisher and the refresh loop.
+#[derive(Clone)]
+pub struct Config {
+    pub redis_host: Arc<String>,
+    pub redis_port: u16,
+    /// Password to authenticate with the Redis server (`AUTH`). `None` connects without
+    /// authentication.
+    pub redis_password: Option<Arc<String>>,
+    /// Connect to the Redis server over TLS (`rediss://`) instead of plaintext.
+    pub redis_tls: bool,
+    pub key_prefix: Arc<String>,
+    pub docker_url: Url,
+    pub enumerate: bool,
+    pub envvar: Arc<String>,
+    pub dry_run: bool,
+    pub expire_seconds: Option<u32>,
+    pub refresh_seconds: Option<u32>,
+    pub docker_network: bool,
+    pub missing_envvar: MissingEnvVarHandling,
+    pub missing_container: MissingContainerHandling,
+    pub systemd: bool,
+    pub watchdog_microseconds: Option<u64>,
+    /// Subscribe to the Docker daemon's container lifecycle event stream (see `Inspect::watch`)
+    /// and react to `start`/`die`/`stop`/`destroy` as they happen, instead of only noticing them
+    /// on the next `refresh_seconds` tick. The timer keeps running alongside it as a slow
+    /// safety-net re-sync, and as the fallback if the event stream isn't available or drops.
+    pub watch: bool,
+    /// Only publish a container's domain-specs while the Docker daemon reports its `HEALTHCHECK`
+    /// status as `healthy` (see `Inspection::health`). A container that flips to `unhealthy` is
+    /// simply skipped on its next refresh pass; it isn't actively unpublished until the following
+    /// `Context::reconcile` notices the host didn't reappear among this cycle's publications and
+    /// prunes it, so removal lags by up to one `refresh_seconds` tick. `--watch` doesn't shorten
+    /// this: health changes aren't among the events it reacts to, and an event-driven refresh
+    /// that skips the container doesn't unpublish it. Containers without a declared healthcheck
+    /// are unaffected, since there's nothing to gate on.
+    pub require_healthy: bool,
+    /// Maximum number of containers to inspect/publish concurrently during a refresh pass.
+    /// `1` (or less) disables the worker pool and refreshes containers one at a time on the
+    /// main thread, same as before this setting existed.
+    pub concurrency: u32,
+    /// Maximum number of times to retry a publish that failed with a transient error (see
+    /// `PublishingError::is_transient`) before giving up and recording it as a `CompanionError`.
+    /// `0` disables retrying entirely.
+    pub publish_max_retries: u32,
+    /// Base delay, in milliseconds, for the exponential backoff between publish retries. The
+    /// actual delay is jittered (a random value between 0 and the computed delay), so this is an
+    /// upper bound on the first retry's wait, not a fixed wait.
+    pub publish_base_delay_ms: u64,
+    /// Upper bound, in milliseconds, on the backoff delay between publish retries, regardless of
+    /// how many attempts have already been made.
+    pub publish_max_delay_ms: u64,
+    /// Whether to auto-provision TLS certificates (via ACME http-01) for discovered domains that
+    /// declare an `https_port`. `acme_directory_url`/`acme_account_key_path`/`acme_contact_email`
+    /// are only consulted when this is set. `companion::run` refuses to start with this set, since this
+    /// build has no working `AcmeTransport` (see `acme::http_transport::HttpAcmeTransport`).
+    pub acme_enabled: bool,
+    /// The ACME directory URL to order certificates from (e.g. Let's Encrypt's).
+    pub acme_directory_url: Option<Url>,
+    /// Path to the ACME account's private key.
+    pub acme_account_key_path: Option<Arc<String>>,
+    /// Contact email registered with the ACME account.
+    pub acme_contact_email: Option<Arc<String>>,
+    /// Re-order a certificate once it's within this many days of expiring.
+    pub acme_renew_within_days: u32,
+    /// `--check` mode (see `::check`): report WARNING once a registration's remaining TTL drops
+    /// below this many seconds.
+    pub check_warn_seconds: u32,
+    /// `--check` mode: report CRITICAL once a registration's remaining TTL drops below this many
+    /// seconds, in addition to a missing registration always being CRITICAL. `0` disables this
+    /// extra threshold.
+    pub check_crit_seconds: u32,
+}
+
+// Implement Default so that tests can build a minimal, valid Config without going through
+// docopt. Mirrors the defaults stated in main's USAGE string.
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            redis_host: Arc::new("localhost".to_owned()),
+            redis_port: 6379,
+            redis_password: None,
+            redis_tls: false,
+            key_prefix: Arc::new("/beachhead/".to_owned()),
+            docker_url: Url::parse("unix://var/run/docker.sock")
+                .expect("default docker_url must parse"),
+            enumerate: false,
+            envvar: Arc::new("BEACHHEAD_DOMAINS".to_owned()),
+            dry_run: false,
+            expire_seconds: Some(60),
+            refresh_seconds: Some(27),
+            docker_network: false,
+            missing_envvar: MissingEnvVarHandling::Automatic,
+            missing_container: MissingContainerHandling::Ignore,
+            systemd: false,
+            watchdog_microseconds: None,
+            watch: false,
+            require_healthy: false,
+            concurrency: 4,
+            publish_max_retries: 4,
+            publish_base_delay_ms: 200,
+            publish_max_delay_ms: 5_000,
+            acme_enabled: false,
+            acme_directory_url: None,
+            acme_account_key_path: None,
+            acme_contact_email: None,
+            acme_renew_within_days: 30,
+            check_warn_seconds: 10,
+            check_crit_seconds: 0,
+        }
+    }
+}
+
+/// Initializes logging for the test suite. Safe (and cheap) to call from every test; only the
+/// first call actually installs the logger.
+#[cfg(test)]
+pub fn init_log() {
+    static INIT: Once = ONCE_INIT;
+    INIT.call_once(|| {
+        let _ = ::env_logger::init();
+    });
+}
+
+/// Consumes a `Result` that can only sensibly occur during start-up, before logging is even
+/// available. Prints the error and exits the process; does nothing on success.
+pub fn stay_calm_and<T, E: Display>(result: Result<T, E>) -> T {
+    match result {
+        Ok(t) => t,
+        Err(e) => {
+            println!("{}", e);
+            ::std::process::exit(1);
+        }
+    }
+}
+
+/// Like [stay_calm_and], but for the outcome of a whole companion run: every error is logged
+/// (they haven't necessarily been logged yet, e.g. in one-shot mode) and the process exits with
+/// a status code derived from how many errors occurred.
+pub fn stay_very_calm_and<T>(result: Result<T, Vec<CompanionError>>) -> T {
+    match result {
+        Ok(t) => t,
+        Err(errors) => {
+            for e in &errors {
+                error!("{}", e);
+            }
+            ::std::process::exit(errors.len() as i32);
+        }
+    }
+}
+
+/// Logs `$e` as an error and then evaluates to `$ret`. Saves a `match`/`if let` at call sites that
+/// just want to record-and-bail on a recoverable error.
+#[macro_export]
+macro_rules! log_err {
+    ($e:expr, $ret:expr) => {{
+        error!("{}", $e);
+        $ret
+    }}
+}
@@ -22,64 +22,206 @@
 
 
 use std;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap, VecDeque};
 use std::error::Error;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::cmp::{Ordering, min};
-use std::rc::Rc;
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::Duration;
 
-use log::LogLevel;
 use chan;
 use chrono::{self, Local, DateTime};
 use chan_signal::Signal;
 use systemd::daemon;
 
-use inspector::{Inspect, Inspection, InspectionError};
+use domain_spec::{DomainSpec, Host};
+use inspector::{Inspect, Inspection, InspectionError, ContainerEvent, ContainerEventKind, HealthStatus};
 use publisher::{Publication, PublishingError, Publish};
+use reporter::{Reporter, Event as ReportEvent};
 use common::{Config, MissingEnvVarHandling, MissingContainerHandling};
+use acme::AcmeManager;
+use acme::InMemoryChallengeResponder;
+use cert_store::{CertStore, CertStoreError, CertifiedKey};
+
+/// Events arriving within this window of each other are coalesced into a single refresh pass,
+/// so that e.g. a `docker-compose up` starting a dozen containers doesn't trigger a dozen
+/// separate inspect/publish round-trips.
+const EVENT_DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// What woke the companion up while it was [Context::wait]ing.
+enum Wake {
+    /// Termination was requested (signal or one-shot mode); shut down.
+    Stop,
+    /// The refresh timer (or watchdog ping, after re-entering the wait) elapsed; run a full
+    /// enumerate/refresh cycle.
+    FullRefresh,
+    /// One or more container lifecycle events arrived, already coalesced per container.
+    Events(Vec<ContainerEvent>),
+}
 
 struct Context {
     pub config: Arc<Config>,
     pub inspector: Box<Inspect>,
     pub publisher: Box<Publish>,
+    pub reporter: Arc<Reporter>,
     pub termination_signal: chan::Receiver<Signal>,
     next_watchdog: Option<chrono::DateTime<chrono::Local>>,
+    event_receiver: chan::Receiver<ContainerEvent>,
+    // Keeps `event_receiver` open forever when `config.watch` is off, the inspector couldn't
+    // subscribe to Docker events, or the event stream dropped (see `wait`), so that `wait`'s
+    // chan_select! always has a (silent) event branch to select on.
+    _event_keepalive: Option<chan::Sender<ContainerEvent>>,
+    // What this companion instance published in the previous full cycle, by host. Only ever
+    // populated from our own successful publications, so reconciliation can never prune a host
+    // owned by some other companion instance sharing the same backend.
+    owned: HashMap<String, Vec<DomainSpec>>,
+    // The (host, specs) most recently published for a container, by container name. Populated by
+    // every single-container refresh (see `refresh_container`), so that a later
+    // `die`/`stop`/`destroy` event for that same container can retract its publication
+    // immediately instead of waiting for `owned`'s slower, full-cycle reconciliation.
+    container_hosts: HashMap<Arc<String>, (String, Vec<DomainSpec>)>,
+    // When the last full refresh cycle completed (reached `reconcile`). `None` until the first
+    // one finishes. Surfaced in `notify_status`'s STATUS string so `systemctl status` shows
+    // freshness at a glance, alongside `owned.len()`.
+    last_refresh_success: Option<DateTime<Local>>,
+    // Provisions/renews TLS certificates for discovered https domains; `None` unless
+    // `config.acme_enabled` (see `run`).
+    acme: Option<AcmeManager>,
+    // Certificates provisioned via `acme`, resolvable by SNI hostname. Stays empty unless
+    // `config.acme_enabled`.
+    cert_store: CertStore,
 }
 
 impl Context {
     fn new(config: Arc<Config>,
-           inspector: Box<Inspect>,
+           mut inspector: Box<Inspect>,
            publisher: Box<Publish>,
+           reporter: Arc<Reporter>,
            termination_signal: chan::Receiver<Signal>)
            -> Context {
         let next_watchdog = config.watchdog_microseconds.map(|_| Local::now());
+        let (event_receiver, event_keepalive) = if config.watch {
+            match inspector.watch() {
+                Ok(recv) => (recv, None),
+                Err(e) => {
+                    warn!("Event-driven refresh unavailable, falling back to polling only. Error: {}",
+                          e);
+                    let (send, recv) = chan::sync(0);
+                    (recv, Some(send))
+                }
+            }
+        } else {
+            let (send, recv) = chan::sync(0);
+            (recv, Some(send))
+        };
         Context {
             config: config,
             termination_signal: termination_signal,
             inspector: inspector,
             publisher: publisher,
+            reporter: reporter,
             next_watchdog: next_watchdog,
+            event_receiver: event_receiver,
+            _event_keepalive: event_keepalive,
+            owned: HashMap::new(),
+            container_hosts: HashMap::new(),
+            last_refresh_success: None,
+            acme: None,
+            cert_store: CertStore::new(),
         }
     }
 
-    fn inspect(&mut self,
-               container_name: Pending<Rc<String>>)
-               -> Result<Pending<Inspection>, CompanionError> {
-        container_name.try_map(|name| self.inspector.inspect(&name)).map_err(From::from)
+    /// Provisions/renews a TLS certificate for every domain in `publication` that declares an
+    /// `https_port`, if ACME support is enabled (see `acme`), and loads it into `cert_store`.
+    /// Provisioning failures are logged but never added to `errors`: a container can still be
+    /// reachable over HTTPS with a pre-existing cert even if this companion couldn't mint a fresh
+    /// one. If, after that, no usable cert+key pair can be resolved for the domain, that *is*
+    /// added to `errors` — publishing a domain we can't actually serve TLS for isn't useful.
+    /// Containers published under an IP literal (see `DomainSpec::host`) are skipped entirely:
+    /// ACME's http-01 challenge validates a DNS name, not an IP, so there's nothing to provision.
+    fn ensure_acme_certificates(&mut self, publication: &Publication, errors: &mut Vec<CompanionError>) {
+        if self.acme.is_none() {
+            return;
+        }
+        for spec in &publication.specs {
+            if spec.https_port.is_none() {
+                continue;
+            }
+            if let Host::Ip(_) = spec.host() {
+                continue;
+            }
+
+            if let Some(ref mut acme) = self.acme {
+                match acme.ensure_certificate(&spec.domain_name) {
+                    Ok(cert) => {
+                        let key = CertifiedKey { cert_pem: cert.cert_pem.clone(), key_pem: cert.key_pem.clone() };
+                        if let Err(e) = self.cert_store.insert(&spec.domain_name, key) {
+                            error!("Failed to load certificate for {} into the cert store. Error: {}",
+                                   spec.domain_name,
+                                   e);
+                        }
+                    }
+                    Err(e) => {
+                        error!("Failed to provision/renew TLS certificate for {}. Error: {}",
+                               spec.domain_name,
+                               e);
+                    }
+                }
+            }
+
+            if self.cert_store.resolve(&spec.domain_name).is_none() {
+                errors.push(CompanionError::from(CertStoreError::MissingCertificate(spec.domain_name.clone())));
+            }
+        }
     }
 
-    fn publish(&mut self, publication: Publication) -> Result<(), CompanionError> {
-        try!(self.publisher.publish(&publication));
-        Ok(())
+    /// Prunes publications from a previous cycle that didn't reappear in `published` (this
+    /// cycle's successful publications), by calling `Publisher::unpublish` for each one. Only
+    /// ever considers hosts this companion itself published before (see `owned`), so it can never
+    /// delete specs managed by another companion instance sharing the same backend. In
+    /// `dry_run` mode, nothing is actually unpublished; candidates are only reported.
+    fn reconcile(&mut self, published: Vec<Publication>) {
+        let current: HashMap<String, Vec<DomainSpec>> =
+            published.into_iter().map(|p| (p.host, p.specs)).collect();
+
+        for (host, specs) in &self.owned {
+            if current.contains_key(host) {
+                continue;
+            }
+
+            if self.config.dry_run {
+                info!("DRY RUN: would unpublish {} ({} domain-spec(s)); no longer published this \
+                       cycle.",
+                      host,
+                      specs.len());
+            } else {
+                match self.publisher.unpublish(host, specs) {
+                    Ok(()) => {
+                        self.reporter.report(ReportEvent::Unpublished {
+                            host: host.clone(),
+                            specs: specs.clone(),
+                        });
+                    }
+                    Err(e) => {
+                        error!("Failed to unpublish stale configuration for host {}. Error: {}",
+                               host,
+                               e);
+                    }
+                }
+            }
+        }
+
+        self.owned = current;
     }
 
     fn enumerate(&mut self,
-                 explicit_container_names: &[Rc<String>])
-                 -> (Vec<Pending<Rc<String>>>, Result<(), CompanionError>) {
-        let mut container_index: HashMap<Rc<String>, Pending<Rc<String>>> = HashMap::new();
+                 explicit_container_names: &[Arc<String>])
+                 -> (Vec<Pending<Arc<String>>>, Result<(), CompanionError>) {
+        let mut container_index: HashMap<Arc<String>, Pending<Arc<String>>> = HashMap::new();
         // Add explicitly listed containers
         for name in explicit_container_names {
-            let key: Rc<String> = name.clone();
+            let key: Arc<String> = name.clone();
             let _ = container_index.insert(key, Pending { explicit: true, todo: name.clone() });
         }
 
@@ -96,7 +238,7 @@ impl Context {
             } else {
                 enum_result = Ok(());
                 for name in enumeration.drain(..) {
-                    let boxed_name = Rc::new(name);
+                    let boxed_name = Arc::new(name);
                     let key = boxed_name.clone();
                     container_index.entry(key)
                         .or_insert(Pending { explicit: false, todo: boxed_name });
@@ -106,116 +248,180 @@ impl Context {
             enum_result = Ok(())
         }
 
+        let explicit_count = container_index.values().filter(|p| p.explicit).count();
+        let implicit_count = container_index.len() - explicit_count;
+        self.reporter.report(ReportEvent::Enumerated { explicit: explicit_count, implicit: implicit_count });
+
         let final_names = container_index.drain().map(|kv| kv.1).collect();
         (final_names, enum_result.map_err(|e| From::from(e)))
     }
 
-    /// Wait for the next refresh. Returns true when we should continue with another refresh;
-    /// Returns false when we should exit (either because we are on one-shot mode or because
-    /// termination was requested)
-    fn wait(&mut self) -> bool {
-        if let Some(refresh_seconds) = self.config.refresh_seconds {
-            let start_of_wait = chrono::Local::now();
-            let timeout_duration = chrono::Duration::seconds(refresh_seconds as i64);
-            let next_refresh = start_of_wait + timeout_duration;
-            let (_s1, refresh_timeout) =
-                deadline_to_alarm_clock(start_of_wait, Some(next_refresh), "refresh");
-
-            loop {
-                // Check signals explicitly. They take precedence over waiting.
-                // This check also handles the case where a signal came in while we were busy
-                // refreshing a container configuration.
-                {
-                    // While parts of self are mutably borrowed, we can't call any functions
-                    // on self. So let's keep mutable borrowing constrained to this region.
-                    let rsig = &mut self.termination_signal;
-                    let signal_received;
-                    // NOTE: default needs to come first (required by macro)
-                    chan_select! {
-                        default => {
-                            signal_received = false;
-                        },
-                        rsig.recv() -> sig => {
-                            debug!("Received {:?} signal. Shutting down.", sig);
-                            signal_received = true;
-                        },
+    /// Wait for the next thing that requires action: the refresh timer elapsing, a batch of
+    /// container lifecycle events arriving, or termination being requested. In one-shot mode
+    /// (`refresh_seconds` disabled) always returns [Wake::Stop] immediately, without consulting
+    /// the event stream.
+    fn wait(&mut self) -> Wake {
+        if self.config.refresh_seconds.is_none() {
+            // Only refresh once and then exit.
+            debug!("Refresh disabled. Shutting down.");
+            return Wake::Stop;
+        }
+        let refresh_seconds = self.config.refresh_seconds.unwrap();
+        let start_of_wait = chrono::Local::now();
+        let timeout_duration = chrono::Duration::seconds(refresh_seconds as i64);
+        let next_refresh = start_of_wait + timeout_duration;
+        let (_s1, refresh_timeout) =
+            deadline_to_alarm_clock(start_of_wait, Some(next_refresh), "refresh");
+
+        loop {
+            // Check signals explicitly. They take precedence over waiting.
+            // This check also handles the case where a signal came in while we were busy
+            // refreshing a container configuration.
+            {
+                // While parts of self are mutably borrowed, we can't call any functions
+                // on self. So let's keep mutable borrowing constrained to this region.
+                let rsig = &mut self.termination_signal;
+                let signal_received;
+                // NOTE: default needs to come first (required by macro)
+                chan_select! {
+                    default => {
+                        signal_received = false;
+                    },
+                    rsig.recv() -> sig => {
+                        debug!("Received {:?} signal. Shutting down.", sig);
+                        signal_received = true;
+                    },
+                }
+                if signal_received {
+                    // Signal end of companion loop
+                    return Wake::Stop;
+                }
+            }
+
+            // Use chan_select! to wait on multiple channels at the same time.
+            // If multiple channels are ready, chan_select! picks an arbitrary channel.
+            // For the watchdog it is not important in which branch we wake up, a refresh
+            // iteration also includes an 'alive' ping. The PING branch is for the situation
+            // where we wake up *just* to satisfy the service manager.
+            let do_next: i32;
+            const DO_STOP: i32 = 0;
+            const DO_CONTINUE: i32 = 1;
+            const DO_EVENTS: i32 = 2;
+            const DO_PING: i32 = 3;
+            let mut first_event = None;
+            {
+                // Compute effective timeouts for the next wait
+                let now = chrono::Local::now();
+                let (_s2, watchdog_timeout) =
+                    deadline_to_alarm_clock(now, self.next_watchdog, "watchdog");
+
+                // Same as above: constrain mutable borrow to the smallest possible regions.
+                let rsig = &mut self.termination_signal;
+                let erecv = &self.event_receiver;
+                chan_select! {
+                    rsig.recv() -> sig => {
+                        debug!("Received {:?} signal. Shutting down.", sig);
+                        do_next = DO_STOP;
+                    },
+                    refresh_timeout.recv() => {
+                        // just continue with the loop
+                        do_next = DO_CONTINUE
+                    },
+                    watchdog_timeout.recv() => {
+                        debug!("Waking up to send 'alive' ping to service manager.");
+                        do_next = DO_PING
+                    },
+                    erecv.recv() -> event => {
+                        first_event = event;
+                        do_next = DO_EVENTS
                     }
-                    if signal_received {
-                        // Signal end of companion loop
-                        return false;
+                };
+            }
+
+            // Act on the outcome of the chan_select!
+            if do_next == DO_STOP {
+                return Wake::Stop;
+            } else if do_next == DO_CONTINUE {
+                return Wake::FullRefresh;
+            } else if do_next == DO_EVENTS {
+                match first_event {
+                    Some(event) => return Wake::Events(self.debounce_events(event)),
+                    // The event stream closed (daemon connection dropped). Fall back to a full
+                    // enumerate/refresh pass so we don't silently miss whatever happened while
+                    // we weren't subscribed. A closed channel would wake every later wait at
+                    // once, so swap in a silent one and rely on the refresh timer from now on.
+                    None => {
+                        debug!("Event stream closed. Falling back to a full refresh.");
+                        let (send, recv) = chan::sync(0);
+                        self.event_receiver = recv;
+                        self._event_keepalive = Some(send);
+                        return Wake::FullRefresh;
                     }
                 }
+            } else if do_next == DO_PING {
+                self.notify_status("Waiting");
+                // no return, re-enter the wait loop
+            } else {
+                error!(concat!("Program error: unexpected state in companion loop: {}. ",
+                               "Expected one of {}, {}, {} or {}"),
+                       do_next,
+                       DO_STOP,
+                       DO_CONTINUE,
+                       DO_EVENTS,
+                       DO_PING);
+                return Wake::Stop;
+            }
+        }
+    }
 
-                // Use chan_select! to wait on multiple channels at the same time.
-                // If multiple channels are ready, chan_select! picks an arbitrary channel.
-                // For the watchdog it is not important in which branch we wake up, a refresh
-                // iteration also includes an 'alive' ping. The PING branch is for the situation
-                // where we wake up *just* to satisfy the service manager.
-                let do_next: i32;
-                const DO_STOP: i32 = 0;
-                const DO_CONTINUE: i32 = 1;
-                const DO_PING: i32 = 3;
-                {
-                    // Compute effective timeouts for the next wait
-                    let now = chrono::Local::now();
-                    let (_s2, watchdog_timeout) =
-                        deadline_to_alarm_clock(now, self.next_watchdog, "watchdog");
-
-                    // Same as above: constrain mutable borrow to the smallest possible regions.
-                    let rsig = &mut self.termination_signal;
-                    chan_select! {
-                        rsig.recv() -> sig => {
-                            debug!("Received {:?} signal. Shutting down.", sig);
-                            do_next = DO_STOP;
-                        },
-                        refresh_timeout.recv() => {
-                            // just continue with the loop
-                            do_next = DO_CONTINUE
-                        },
-                        watchdog_timeout.recv() => {
-                            debug!("Waking up to send 'alive' ping to service manager.");
-                            do_next = DO_PING
+    /// Coalesces `first` with whatever further container events arrive within
+    /// [EVENT_DEBOUNCE], collapsing repeated events for the same container down to the most
+    /// recent one. This keeps a burst of `docker-compose up`/`down` activity from triggering a
+    /// separate refresh per container.
+    fn debounce_events(&mut self, first: ContainerEvent) -> Vec<ContainerEvent> {
+        let mut by_container = HashMap::new();
+        by_container.insert(first.container_name.clone(), first);
+
+        let deadline = chan::after(EVENT_DEBOUNCE);
+        loop {
+            let erecv = &self.event_receiver;
+            chan_select! {
+                erecv.recv() -> event => {
+                    match event {
+                        Some(event) => {
+                            by_container.insert(event.container_name.clone(), event);
                         }
-                    };
-                }
-
-                // Act on the outcome of the chan_select!
-                if do_next == DO_STOP {
-                    return false;
-                } else if do_next == DO_CONTINUE {
-                    return true;
-                } else if do_next == DO_PING {
-                    self.notify_status("Waiting");
-                    // no return, re-enter the wait loop
-                } else {
-                    error!(concat!("Program error: unexpected state in companion loop: {}. ",
-                                   "Expected one of {}, {} or {}"),
-                           do_next,
-                           DO_STOP,
-                           DO_CONTINUE,
-                           DO_PING);
-                    return false;
-                }
+                        None => break,
+                    }
+                },
+                deadline.recv() => break,
             }
-        } else {
-            // Only refresh once and then exit.
-            debug!("Refresh disabled. Shutting down.");
-            false
         }
+
+        by_container.drain().map(|kv| kv.1).collect()
     }
 
     /// Sends a notification to the systemd service manager. This notification will include two
-    /// fields: the supplied status (for display by the service manager) and a watchdog 'alive'
-    /// ping.
+    /// fields: a `STATUS` built from `phase` plus how many containers are currently registered
+    /// and when the last full refresh cycle completed (so `systemctl status` shows a live,
+    /// informative one-liner instead of just `phase`), and a watchdog 'alive' ping.
     /// It also resets the internal deadline for the next watchdog alive ping.
-    fn notify_status(&mut self, status: &str) {
+    fn notify_status(&mut self, phase: &str) {
         if self.config.systemd {
             // Take the time now before the systemd call. That automatically makes our watchdog
             // deadline conservative (relevant in case the systemd call somehow takes a significant
             // amount of time).
             let now = Local::now();
+            let last_refresh = match self.last_refresh_success {
+                Some(t) => t.to_string(),
+                None => "never".to_owned(),
+            };
+            let status = format!("{} ({} container(s) registered, last refresh: {})",
+                                  phase,
+                                  self.owned.len(),
+                                  last_refresh);
             debug!("Notifying service manager. WATCHDOG=1, STATUS={}, now={}", status, now);
-            let alive = [(daemon::STATE_STATUS, status), (daemon::STATE_WATCHDOG, "1")];
+            let alive = [(daemon::STATE_STATUS, status.as_str()), (daemon::STATE_WATCHDOG, "1")];
             if let Err(e) = notify(&alive) {
                 // Not 100% sure what to do in this situation. If we can't send the alive ping, we
                 // will probably be killed soon (might want to exit gracefully).
@@ -271,8 +477,20 @@ fn deadline_to_alarm_clock(now: DateTime<Local>,
     }
 }
 
+/// Converts an inspection into the publication to send to the backend, collapsing redundant
+/// specs (e.g. the same domain advertised twice with an explicit and an implicit default port)
+/// via `DomainSpec`'s canonical identity so we don't publish duplicate entries for one domain,
+/// and backfilling each spec's external ports (see `DomainSpec::with_effective_external_ports`)
+/// so a consumer reading the published spec always sees an explicit external port.
 fn to_publication(inspection: Pending<Inspection>) -> Publication {
-    Publication { host: inspection.todo.host, specs: inspection.todo.specs }
+    let mut seen = BTreeSet::new();
+    let specs = inspection.todo
+        .specs
+        .into_iter()
+        .filter(|s| seen.insert(s.clone()))
+        .map(DomainSpec::with_effective_external_ports)
+        .collect();
+    Publication { host: inspection.todo.host, specs: specs }
 }
 
 fn notify(entries: &[(&str, &str)]) -> Result<(), CompanionError> {
@@ -291,10 +509,18 @@ pub const STATE_STOPPING: &'static str = "STOPPING";
 pub fn run(config: Arc<Config>,
            inspector: Box<Inspect>,
            publisher: Box<Publish>,
+           reporter: Arc<Reporter>,
            termination_signal: chan::Receiver<Signal>,
-           explicit_container_names: &Vec<Rc<String>>)
+           explicit_container_names: &Vec<Arc<String>>)
            -> Result<(), Vec<CompanionError>> {
-    let mut ctx = Context::new(config.clone(), inspector, publisher, termination_signal);
+    if config.acme_enabled {
+        // HttpAcmeTransport unconditionally returns AcmeError::Unsupported (no HTTP client or
+        // JOSE/JWS-signing dependency in this build yet), so wiring it up here would just fail
+        // every https domain's order, forever, on every refresh cycle. Refuse to start instead of
+        // shipping a flag that can never succeed.
+        return Err(vec![CompanionError::AcmeUnsupported]);
+    }
+    let mut ctx = Context::new(config.clone(), inspector, publisher, reporter, termination_signal);
     info!("Companion initialized.");
     if config.systemd {
         if let Err(e) = notify(&[(daemon::STATE_READY, "1")]) {
@@ -304,133 +530,445 @@ pub fn run(config: Arc<Config>,
         }
     }
 
-    loop {
-        debug!("Start iteration.");
-        ctx.notify_status("Refreshing");
-
-        // Errors that occurred in this iteration.
-        let mut errors = Vec::new();
+    // Errors that occurred in the last iteration, whether that was a full cycle or an
+    // events-only wake-up. Declared outside the loop so that `Wake::Stop` can return them.
+    let mut errors = Vec::new();
+    // Whether the next iteration should do a full enumerate+refresh pass (the original,
+    // poll-driven behaviour) or whether it can go straight back to waiting because the previous
+    // wake-up was already handled by refreshing just the affected container(s).
+    let mut do_full_cycle = true;
 
-        // Combine explicitly listed names with containers obtained from enumeration.
-        let names = {
-            let (names, enum_result) = ctx.enumerate(explicit_container_names);
-            if let Err(e) = enum_result {
-                errors.push(e)
-            }
-            names
-        };
-        debug!("Enumerated containers: {:#?}", names);
+    loop {
+        if do_full_cycle {
+            debug!("Start full refresh cycle.");
+            ctx.notify_status("Refreshing");
+            errors.clear();
 
-        // Refresh each of the containers.
-        for name in names.into_iter() {
-            refresh_container(name, &mut errors, &mut ctx);
+            // Combine explicitly listed names with containers obtained from enumeration.
+            let names = {
+                let (names, enum_result) = ctx.enumerate(explicit_container_names);
+                if let Err(e) = enum_result {
+                    errors.push(e)
+                }
+                names
+            };
+            debug!("Enumerated containers: {:#?}", names);
+
+            // Refresh each of the containers, fanning out across a bounded worker pool when the
+            // backend supports it (see `refresh_many`).
+            let mut published = Vec::new();
+            refresh_many(names, &mut errors, &mut published, &mut ctx);
+
+            // Prune anything we published in a previous cycle that didn't show up in this one.
+            ctx.reconcile(published);
+            ctx.last_refresh_success = Some(Local::now());
         }
 
         ctx.notify_status("Waiting");
-        // Wait for refresh timeout or external abort (kill signal).
-        // Returns immediately if we are only supposed to run once.
-        if ctx.wait() {
-            // We only return the errors from the last iteration. All errors have been logged.
-            errors.clear();
-        } else {
-            // We are shutting down. This can have various reasons. Maybe we are in run-once mode
-            // or maybe we received a signal.
-            if config.systemd {
-                let shutdown = [(daemon::STATE_STATUS, "Stopping"), (STATE_STOPPING, "1")];
-                if let Err(e) = notify(&shutdown) {
-                    warn!(concat!("Failed to update service status in systemd service manager \
-                                   (notify)",
-                                  "before shutting down. Error: {}"),
-                          e);
+        // Wait for refresh timeout, a batch of container events, or external abort (kill
+        // signal). Returns immediately if we are only supposed to run once.
+        match ctx.wait() {
+            Wake::FullRefresh => {
+                do_full_cycle = true;
+            }
+            Wake::Events(events) => {
+                debug!("Woke up for {} container event(s): {:#?}", events.len(), events);
+                do_full_cycle = false;
+                errors.clear();
+                for event in events {
+                    refresh_from_event(event, explicit_container_names, &mut errors, &mut ctx);
                 }
             }
+            Wake::Stop => {
+                // We are shutting down. This can have various reasons. Maybe we are in run-once
+                // mode or maybe we received a signal.
+                if config.systemd {
+                    let shutdown = [(daemon::STATE_STATUS, "Stopping"), (STATE_STOPPING, "1")];
+                    if let Err(e) = notify(&shutdown) {
+                        warn!(concat!("Failed to update service status in systemd service ",
+                                      "manager (notify) before shutting down. Error: {}"),
+                              e);
+                    }
+                }
 
-            // Return errors from the last iteration. This is mainly useful for the case where
-            // we only run once. Lets the tool set an appropriate status code on program exit.
-            if errors.is_empty() || config.refresh_seconds.is_some() {
-                return Ok(());
-            } else {
-                return Err(errors);
+                // Return errors from the last iteration. This is mainly useful for the case where
+                // we only run once. Lets the tool set an appropriate status code on program exit.
+                if errors.is_empty() || config.refresh_seconds.is_some() {
+                    return Ok(());
+                } else {
+                    return Err(errors);
+                }
+            }
+        }
+    }
+}
+
+/// Reacts to a single container lifecycle event coming out of the Docker event stream.
+/// `start`/`unpause` triggers an immediate inspect+publish of just that container, skipping the
+/// rest of the fleet entirely. `die`/`stop`/`destroy` means the container is gone; the companion
+/// stops refreshing it and, if it has a known publication (see `Context::container_hosts`),
+/// retracts it immediately instead of leaving it to expire on its own TTL.
+fn refresh_from_event(event: ContainerEvent,
+                      explicit_container_names: &[Arc<String>],
+                      errors: &mut Vec<CompanionError>,
+                      ctx: &mut Context) {
+    match event.kind {
+        ContainerEventKind::Start | ContainerEventKind::Unpause => {
+            let explicit = explicit_container_names.iter().any(|n| *n == event.container_name);
+            debug!("Container {} {:?}. Refreshing.", event.container_name, event.kind);
+            refresh_container(Pending { explicit: explicit, todo: event.container_name },
+                              errors,
+                              &mut Vec::new(),
+                              ctx);
+        }
+        ContainerEventKind::Die | ContainerEventKind::Stop | ContainerEventKind::Destroy => {
+            match ctx.container_hosts.remove(&event.container_name) {
+                Some((host, specs)) => {
+                    ctx.owned.remove(&host);
+                    if ctx.config.dry_run {
+                        info!("DRY RUN: would unpublish {} ({} domain-spec(s)); container {} {:?}.",
+                              host,
+                              specs.len(),
+                              event.container_name,
+                              event.kind);
+                    } else {
+                        match ctx.publisher.unpublish(&host, &specs) {
+                            Ok(()) => {
+                                ctx.reporter.report(ReportEvent::Unpublished {
+                                    host: host.clone(),
+                                    specs: specs.clone(),
+                                });
+                                info!("Container {} {:?}. Unpublished {}.",
+                                      event.container_name,
+                                      event.kind,
+                                      host);
+                            }
+                            Err(e) => {
+                                error!("Failed to unpublish {} after container {} {:?}. Error: {}",
+                                       host,
+                                       event.container_name,
+                                       event.kind,
+                                       e);
+                                errors.push(CompanionError::from(e));
+                            }
+                        }
+                    }
+                }
+                None => {
+                    info!("Container {} {:?}, but it had no known publication to retract.",
+                          event.container_name,
+                          event.kind);
+                }
             }
         }
     }
 }
 
-/// Inspect and publish updates for the indicated container.
-/// If errors happen along the way it will primarily be reported to the log.
+/// Inspect and publish updates for the indicated container, using the given context's inspector,
+/// publisher and reporter. Thin wrapper around [refresh_one] for the (common) sequential,
+/// single-context case; see [refresh_many] for the bounded-concurrency fan-out used for a full
+/// refresh cycle. Anything successfully published is appended to `published`, for callers that
+/// feed a full cycle's results into [Context::reconcile].
+fn refresh_container(name: Pending<Arc<String>>,
+                     errors: &mut Vec<CompanionError>,
+                     published: &mut Vec<Publication>,
+                     ctx: &mut Context) {
+    let config = ctx.config.clone();
+    let container = name.todo.clone();
+    if let Some(p) = refresh_one(name, errors, &mut *ctx.inspector, &mut *ctx.publisher, &*ctx.reporter, &config) {
+        ctx.ensure_acme_certificates(&p, errors);
+        ctx.container_hosts.insert(container, (p.host.clone(), p.specs.clone()));
+        published.push(p);
+    }
+}
+
+/// Inspect and publish updates for the indicated container. Progress and outcome are surfaced
+/// via `reporter` (see [::reporter::Reporter]) instead of logging directly.
 /// Errors that are considered 'problematic' (according to configuration) will *additionally*
 /// be appended to the `errors` list.
 /// Unless you are interested whether a *particular* refresh was successful, you don't need
-/// to do anything with these error values (they have already been logged).
-fn refresh_container(name: Pending<Rc<String>>,
-                     errors: &mut Vec<CompanionError>,
-                     ctx: &mut Context) {
+/// to do anything with these error values (the reporter has already surfaced them).
+/// Returns the [Publication] that was (successfully) published, for the caller to feed into
+/// reconciliation (see [Context::reconcile]); `None` if nothing was published this time (the
+/// container was skipped or failed). In a dry run, returns the publication that would have been
+/// published, without touching the backend.
+fn refresh_one(name: Pending<Arc<String>>,
+               errors: &mut Vec<CompanionError>,
+               inspector: &mut Inspect,
+               publisher: &mut Publish,
+               reporter: &Reporter,
+               config: &Config)
+               -> Option<Publication> {
     let current_container = name.todo.clone();
     let was_explicit = name.explicit;
-    let config = ctx.config.clone();
 
     // Retrieve requested configuration from the container.
-    debug!("Inspect {}", current_container);
-    let inspection = match ctx.inspect(name) {
+    reporter.report(ReportEvent::Inspecting { container: current_container.clone() });
+    let inspection = match name.try_map(|name| inspector.inspect(&name)).map_err(CompanionError::from) {
         // Depending on how the companion is configured, an inspection error has different
         // consequences.
         Err(e) => {
-            let level;
-            let consider_error;
-            if config.missing_container == MissingContainerHandling::Report {
-                level = LogLevel::Error;
-                consider_error = true
+            let consider_error = if config.missing_container == MissingContainerHandling::Report {
+                true
             } else if was_explicit {
-                level = LogLevel::Warn;
-                consider_error = true
+                true
             } else {
-                level = LogLevel::Info;
-                consider_error = false
-            }
-            log!(level, "Failed to inspect {}. Skipping. Error: {}", current_container, e);
+                false
+            };
             if consider_error {
+                reporter.report(ReportEvent::Failed {
+                    container: current_container.clone(),
+                    error: format!("{}", e),
+                });
                 errors.push(e)
+            } else {
+                reporter.report(ReportEvent::Skipped {
+                    container: current_container.clone(),
+                    reason: format!("failed to inspect: {}", e),
+                });
             }
 
             // Need to skip the update for this container (inspection failed)
-            return;
+            return None;
         }
         Ok(x) => x,
     };
 
     // Handle missing env var
     if !inspection.todo.envvar_present {
-        let level;
         match (was_explicit, config.missing_envvar) {
             (true, MissingEnvVarHandling::Automatic) |
             (_, MissingEnvVarHandling::Report) => {
-                level = LogLevel::Error;
-                errors.push(CompanionError::EnvVarMissing(current_container.clone(),
-                                                          config.envvar.to_owned()))
+                let e = CompanionError::EnvVarMissing(current_container.clone(), config.envvar.to_owned());
+                reporter.report(ReportEvent::Failed {
+                    container: current_container.clone(),
+                    error: format!("{}", e),
+                });
+                errors.push(e)
+            }
+            (_, _) => {
+                reporter.report(ReportEvent::Skipped {
+                    container: current_container.clone(),
+                    reason: format!("environment variable '{}' not set", config.envvar),
+                });
             }
-            (_, _) => level = LogLevel::Info,
         }
-        log!(level,
-             "No environment variable '{}' configured for container {}. Skipping.",
-             config.envvar,
-             current_container);
-        return;
+        return None;
+    }
+
+    // Handle unhealthy containers. Not publishing is enough to make the container's
+    // registration go away: a full refresh cycle's `Context::reconcile` prunes anything that
+    // was published before but isn't this time around.
+    if config.require_healthy && !inspection.todo.health.is_healthy() {
+        reporter.report(ReportEvent::Skipped {
+            container: current_container.clone(),
+            reason: format!("container health status is {:?}, not healthy", inspection.todo.health),
+        });
+        return None;
     }
 
     // Publish updated configuration
     let publication = to_publication(inspection);
 
     if config.dry_run {
-        info!("DRY RUN: would update {} with {:#?}", current_container, publication)
+        // Dry runs don't touch the backend, but `publication` is still what *would* be live, so
+        // reconciliation (see `Context::reconcile`) has an accurate picture of this cycle.
+        info!("DRY RUN: would update {} with {:#?}", current_container, publication);
+        Some(publication)
+    } else if publish_with_retry(&publication, publisher, reporter, &current_container, config, errors) {
+        Some(publication)
     } else {
-        info!("Updating configuration for container {}. Publishing {:?}",
-              current_container,
-              publication);
-        if let Err(e) = ctx.publish(publication) {
-            error!("Failed to publish updated configuration for container '{}'. Error: {}",
-                   current_container,
-                   e);
-            errors.push(e);
+        None
+    }
+}
+
+/// Publishes `publication`, retrying transient failures (see `PublishingError::is_transient`)
+/// with exponential backoff and full jitter before giving up. Permanent failures are reported and
+/// recorded into `errors` immediately, without burning a retry. Returns whether the publication
+/// ultimately succeeded.
+fn publish_with_retry(publication: &Publication,
+                      publisher: &mut Publish,
+                      reporter: &Reporter,
+                      container: &Arc<String>,
+                      config: &Config,
+                      errors: &mut Vec<CompanionError>)
+                      -> bool {
+    let mut rng = XorShiftRng::from_time();
+    let mut attempt = 0;
+    loop {
+        match publisher.publish(publication) {
+            Ok(()) => {
+                reporter.report(ReportEvent::Published {
+                    host: publication.host.clone(),
+                    specs: publication.specs.clone(),
+                });
+                return true;
+            }
+            Err(e) => {
+                if e.is_transient() && attempt < config.publish_max_retries {
+                    let delay = backoff_delay(attempt,
+                                              config.publish_base_delay_ms,
+                                              config.publish_max_delay_ms,
+                                              &mut rng);
+                    reporter.report(ReportEvent::Skipped {
+                        container: container.clone(),
+                        reason: format!("transient publish error (retry {}/{} in {}ms): {}",
+                                        attempt + 1,
+                                        config.publish_max_retries,
+                                        delay.as_secs() * 1000 +
+                                        (delay.subsec_nanos() / 1_000_000) as u64,
+                                        e),
+                    });
+                    thread::sleep(delay);
+                    attempt += 1;
+                } else {
+                    let e = CompanionError::from(e);
+                    reporter.report(ReportEvent::Failed {
+                        container: container.clone(),
+                        error: format!("{}", e),
+                    });
+                    errors.push(e);
+                    return false;
+                }
+            }
+        }
+    }
+}
+
+/// Delay before retry attempt `attempt` (0-based): exponential backoff (`base * 2^attempt`,
+/// capped at `max_delay_ms`) with full jitter, i.e. a uniformly random value between zero and
+/// that cap. See https://aws.amazon.com/blogs/architecture/exponential-backoff-and-jitter/
+fn backoff_delay(attempt: u32, base_delay_ms: u64, max_delay_ms: u64, rng: &mut XorShiftRng) -> Duration {
+    let delay_ms = base_delay_ms.saturating_mul(1u64 << min(attempt, 32)).min(max_delay_ms);
+    Duration::from_millis(rng.next_below(delay_ms + 1))
+}
+
+/// Minimal seedable xorshift PRNG used only to jitter retry delays; not suitable for anything
+/// security-sensitive. Deterministic given a seed, which is what keeps [backoff_delay]'s tests
+/// reproducible.
+struct XorShiftRng(u64);
+
+impl XorShiftRng {
+    fn new(seed: u64) -> XorShiftRng {
+        XorShiftRng(if seed == 0 { 0xdead_beef } else { seed })
+    }
+
+    /// Seeds from the current time, for the (non-test) production case where we just need the
+    /// retries of different containers/attempts to not all line up.
+    fn from_time() -> XorShiftRng {
+        use std::time::{SystemTime, UNIX_EPOCH};
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.subsec_nanos())
+            .unwrap_or(0);
+        XorShiftRng::new(nanos as u64)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.0 = x;
+        x
+    }
+
+    /// Returns a value in `[0, bound)`; `0` if `bound` is `0`.
+    fn next_below(&mut self, bound: u64) -> u64 {
+        if bound == 0 {
+            0
+        } else {
+            self.next_u64() % bound
+        }
+    }
+}
+
+/// Runs a full refresh pass over `names`, fanning the work out across up to
+/// `config.concurrency` worker threads so inspect/publish round-trips for independent
+/// containers overlap instead of serializing. Every container is attempted exactly once;
+/// errors from every worker are collected into `errors` regardless of completion order.
+/// Everything successfully published is appended to `published`, so the caller can feed a
+/// complete cycle's results into [Context::reconcile].
+///
+/// Falls back to refreshing sequentially on the calling thread (the original behaviour) when
+/// concurrency is disabled (`concurrency <= 1`) or when the configured inspector/publisher
+/// can't produce independent handles for the workers (e.g. the test doubles used throughout
+/// this module's test suite, which only make sense with a single shared instance).
+fn refresh_many(names: Vec<Pending<Arc<String>>>,
+                errors: &mut Vec<CompanionError>,
+                published: &mut Vec<Publication>,
+                ctx: &mut Context) {
+    let worker_count = min(ctx.config.concurrency as usize, names.len());
+
+    if worker_count <= 1 {
+        for name in names {
+            refresh_container(name, errors, published, ctx);
+        }
+        return;
+    }
+
+    let mut handles = Vec::with_capacity(worker_count);
+    for _ in 0..worker_count {
+        match (ctx.inspector.clone_handle(), ctx.publisher.clone_handle()) {
+            (Some(inspector), Some(publisher)) => handles.push((inspector, publisher)),
+            _ => break,
+        }
+    }
+
+    if handles.len() < worker_count {
+        debug!(concat!("Configured inspector/publisher doesn't support cloned handles; ",
+                       "falling back to sequential refresh."));
+        for name in names {
+            refresh_container(name, errors, published, ctx);
+        }
+        return;
+    }
+
+    let config = ctx.config.clone();
+    let reporter = ctx.reporter.clone();
+    let queue = Arc::new(Mutex::new(names.into_iter().collect::<VecDeque<_>>()));
+
+    let workers: Vec<JoinHandle<(Vec<CompanionError>, Vec<(Arc<String>, Publication)>)>> = handles.into_iter()
+        .map(|(mut inspector, mut publisher)| {
+            let queue = queue.clone();
+            let config = config.clone();
+            let reporter = reporter.clone();
+            thread::spawn(move || {
+                let mut worker_errors = Vec::new();
+                let mut worker_published = Vec::new();
+                loop {
+                    let next = queue.lock().expect("refresh queue mutex poisoned").pop_front();
+                    match next {
+                        Some(name) => {
+                            let container = name.todo.clone();
+                            let p = refresh_one(name,
+                                                &mut worker_errors,
+                                                &mut *inspector,
+                                                &mut *publisher,
+                                                &*reporter,
+                                                &config);
+                            worker_published.extend(p.map(|p| (container, p)));
+                        }
+                        None => break,
+                    }
+                }
+                (worker_errors, worker_published)
+            })
+        })
+        .collect();
+
+    for worker in workers {
+        match worker.join() {
+            Ok((worker_errors, worker_published)) => {
+                errors.extend(worker_errors);
+                // Same bookkeeping as `refresh_container` does for a sequential refresh.
+                for (container, p) in worker_published {
+                    ctx.ensure_acme_certificates(&p, errors);
+                    ctx.container_hosts.insert(container, (p.host.clone(), p.specs.clone()));
+                    published.push(p);
+                }
+            }
+            Err(_) => error!("A refresh worker thread panicked; its containers were not retried."),
         }
     }
 }
@@ -493,7 +1031,7 @@ quick_error! {
             from()
             display(me) -> ("{} Error: {}", me.description(), err)
         }
-        EnvVarMissing(container_name: Rc<String>, envvar: Rc<String>) {
+        EnvVarMissing(container_name: Arc<String>, envvar: Arc<String>) {
             description("Configured environment variable missing on container.")
             display(err) -> ("{} container name: {}, environment variable: {}",
                 err.description(), container_name, envvar)
@@ -503,6 +1041,17 @@ quick_error! {
             cause(err)
             display(me) -> ("{} Error: {}", me.description(), err)
         }
+        CertStore(err: CertStoreError) {
+            description("Error resolving a TLS certificate for a published domain.")
+            cause(err)
+            from()
+            display(me) -> ("{} Error: {}", me.description(), err)
+        }
+        AcmeUnsupported {
+            description(concat!("--acme was given, but this build has no working ACME transport ",
+                                "(see acme::http_transport::HttpAcmeTransport): every order would ",
+                                "fail forever instead of provisioning a certificate."))
+        }
     }
 }
 
@@ -511,7 +1060,6 @@ quick_error! {
 #[allow(unused_variables, unused_imports)]
 mod tests {
     use std::sync::Arc;
-    use std::rc::Rc;
     use std::cell::RefCell;
     use std::ops::Deref;
 
@@ -523,8 +1071,9 @@ mod tests {
     use common::{self, Config, MissingEnvVarHandling, MissingContainerHandling};
     use ::inspector::mock_inspector::{MockInspector, FakeError};
     use ::inspector::Inspection;
-    use ::domain_spec::DomainSpec;
+    use ::acme::mock_transport::MockTransport;
     use ::publisher::mock_publisher::{MockPublisher, MockError};
+    use ::reporter::text::TextReporter;
 
     #[test]
     fn empty() {
@@ -537,6 +1086,7 @@ mod tests {
         let ctx = Context::new(cfg,
                                Box::new(MockInspector::default()),
                                Box::new(MockPublisher::default()),
+                               Arc::new(TextReporter),
                                term_recv);
 
         // #### THEN  ####
@@ -553,13 +1103,14 @@ mod tests {
         let mut ctx = Context::new(Arc::new(cfg),
                                    Box::new(MockInspector::default()),
                                    Box::new(MockPublisher::default()),
+                                   Arc::new(TextReporter),
                                    term_recv);
 
         // #### WHEN  ####
-        let do_continue = ctx.wait();
+        let wake = ctx.wait();
 
         // #### THEN  ####
-        assert!(!do_continue, "One shot companion context tried to run more than once.");
+        assert!(is_stop(&wake), "One shot companion context tried to run more than once.");
     }
 
     #[test]
@@ -572,14 +1123,15 @@ mod tests {
         let mut ctx = Context::new(Arc::new(cfg),
                                    Box::new(MockInspector::default()),
                                    Box::new(MockPublisher::default()),
+                                   Arc::new(TextReporter),
                                    term_recv);
 
         // #### WHEN  ####
         term_send.send(Signal::INT);
-        let do_continue = ctx.wait();
+        let wake = ctx.wait();
 
         // #### THEN  ####
-        assert!(!do_continue,
+        assert!(is_stop(&wake),
                 concat!("Companion context tried to run after ", "termination was requested."));
     }
 
@@ -593,14 +1145,15 @@ mod tests {
         let mut ctx = Context::new(Arc::new(cfg),
                                    Box::new(MockInspector::default()),
                                    Box::new(MockPublisher::default()),
+                                   Arc::new(TextReporter),
                                    term_recv);
 
         // #### WHEN  ####
         term_send.send(Signal::TERM);
-        let do_continue = ctx.wait();
+        let wake = ctx.wait();
 
         // #### THEN  ####
-        assert!(!do_continue,
+        assert!(is_stop(&wake),
                 concat!("Companion context tried to run after ", "termination was requested."));
     }
 
@@ -614,13 +1167,91 @@ mod tests {
         let mut ctx = Context::new(Arc::new(cfg),
                                    Box::new(MockInspector::default()),
                                    Box::new(MockPublisher::default()),
+                                   Arc::new(TextReporter),
                                    term_recv);
 
         // #### WHEN  ####
-        let do_continue = ctx.wait();
+        let wake = ctx.wait();
 
         // #### THEN  ####
-        assert!(do_continue, "Refresh should be successful.");
+        assert!(is_full_refresh(&wake), "Refresh should be successful.");
+    }
+
+    #[test]
+    fn wait_event_triggers_single_container_refresh() {
+        common::init_log();
+        // #### GIVEN ####
+        let (term_send, term_recv) = chan::sync(1);
+        let mut cfg = Config::default();
+        cfg.refresh_seconds = Some(60);
+        cfg.watch = true;
+        let (event_send, event_recv) = chan::async();
+        let mut inspector = MockInspector::default();
+        inspector.watch_events = Some(event_recv);
+        let mut ctx = Context::new(Arc::new(cfg),
+                                   Box::new(inspector),
+                                   Box::new(MockPublisher::default()),
+                                   Arc::new(TextReporter),
+                                   term_recv);
+        let gamma = Arc::new("gamma".to_owned());
+
+        // #### WHEN  ####
+        event_send.send(ContainerEvent { container_name: gamma.clone(), kind: ContainerEventKind::Start });
+        let wake = ctx.wait();
+
+        // #### THEN  ####
+        match wake {
+            Wake::Events(events) => {
+                assert_eq!(events.len(), 1);
+                assert_eq!(events[0].container_name, gamma);
+                assert_eq!(events[0].kind, ContainerEventKind::Start);
+            }
+            _ => assert!(false, "Expected Wake::Events, got something else."),
+        }
+    }
+
+    #[test]
+    fn wait_ignores_events_when_watch_is_disabled() {
+        common::init_log();
+        // #### GIVEN ####
+        let (term_send, term_recv) = chan::sync(1);
+        let mut cfg = Config::default();
+        cfg.refresh_seconds = Some(1);
+        let (event_send, event_recv) = chan::async();
+        let mut inspector = MockInspector::default();
+        inspector.watch_events = Some(event_recv);
+        let mut ctx = Context::new(Arc::new(cfg),
+                                   Box::new(inspector),
+                                   Box::new(MockPublisher::default()),
+                                   Arc::new(TextReporter),
+                                   term_recv);
+
+        // #### WHEN  ####
+        // Even though the mock inspector has events ready to hand out, `config.watch` is off, so
+        // `Context::new` never subscribed and this event is never picked up.
+        event_send.send(ContainerEvent {
+            container_name: Arc::new("gamma".to_owned()),
+            kind: ContainerEventKind::Start,
+        });
+        let wake = ctx.wait();
+
+        // #### THEN  ####
+        assert!(is_full_refresh(&wake),
+                "Should fall back to the refresh timer, not react to the event.");
+    }
+
+    fn is_stop(wake: &Wake) -> bool {
+        match *wake {
+            Wake::Stop => true,
+            _ => false,
+        }
+    }
+
+    fn is_full_refresh(wake: &Wake) -> bool {
+        match *wake {
+            Wake::FullRefresh => true,
+            _ => false,
+        }
     }
 
     #[test]
@@ -633,9 +1264,10 @@ mod tests {
         let mut ctx = Context::new(Arc::new(cfg),
                                    Box::new(MockInspector::default()),
                                    Box::new(MockPublisher::default()),
+                                   Arc::new(TextReporter),
                                    term_recv);
-        let alpha = Rc::new("alpha".to_owned());
-        let beta = Rc::new("beta".to_owned());
+        let alpha = Arc::new("alpha".to_owned());
+        let beta = Arc::new("beta".to_owned());
         let explicit_containers = vec![alpha.clone(), beta.clone()];
 
         // #### WHEN  ####
@@ -658,12 +1290,13 @@ mod tests {
         let mut cfg = Config::default();
         cfg.enumerate = true;
         let mut inspector = MockInspector::default();
-        let alpha = Rc::new("alpha".to_owned());
-        let beta = Rc::new("beta".to_owned());
+        let alpha = Arc::new("alpha".to_owned());
+        let beta = Arc::new("beta".to_owned());
         inspector.enumerate_result = Ok(vec![(*alpha).clone(), (*beta).clone()]);
         let mut ctx = Context::new(Arc::new(cfg),
                                    Box::new(inspector),
                                    Box::new(MockPublisher::default()),
+                                   Arc::new(TextReporter),
                                    term_recv);
         let explicit_containers = Vec::new();
 
@@ -687,15 +1320,16 @@ mod tests {
         let mut cfg = Config::default();
         cfg.enumerate = true;
         let mut inspector = MockInspector::default();
-        let alpha = Rc::new("alpha".to_owned());
-        let beta = Rc::new("beta".to_owned());
+        let alpha = Arc::new("alpha".to_owned());
+        let beta = Arc::new("beta".to_owned());
         inspector.enumerate_result = Ok(vec![(*alpha).clone(), (*beta).clone()]);
         let mut ctx = Context::new(Arc::new(cfg),
                                    Box::new(inspector),
                                    Box::new(MockPublisher::default()),
+                                   Arc::new(TextReporter),
                                    term_recv);
-        let gamma = Rc::new("gamma".to_owned());
-        let delta = Rc::new("delta".to_owned());
+        let gamma = Arc::new("gamma".to_owned());
+        let delta = Arc::new("delta".to_owned());
         let explicit_containers = vec![gamma.clone(), delta.clone()];
 
         // #### WHEN  ####
@@ -731,9 +1365,10 @@ mod tests {
         let mut ctx = Context::new(Arc::new(cfg),
                                    Box::new(inspector),
                                    Box::new(MockPublisher::default()),
+                                   Arc::new(TextReporter),
                                    term_recv);
-        let alpha = Rc::new("alpha".to_owned());
-        let beta = Rc::new("beta".to_owned());
+        let alpha = Arc::new("alpha".to_owned());
+        let beta = Arc::new("beta".to_owned());
         let explicit_containers = vec![alpha.clone(), beta.clone()];
 
         // #### WHEN  ####
@@ -765,23 +1400,28 @@ mod tests {
         let publisher = Arc::new(RefCell::new(MockPublisher::default()));
 
         // mock inspector
-        let beta = Rc::new("beta".to_owned());
+        let beta = Arc::new("beta".to_owned());
         let mut inspector = MockInspector::default();
         let spec1 = DomainSpec {
             domain_name: "one.beta.domain".to_owned(),
             http_port: Some(80),
             https_port: Some(443),
+            http_external_port: None,
+            https_external_port: None,
         };
         let spec2 = DomainSpec {
             domain_name: "two.beta.domain".to_owned(),
             http_port: Some(8080),
             https_port: None,
+            http_external_port: None,
+            https_external_port: None,
         };
         inspector.inspect_results.insert(beta.clone(),
                                          Ok(Inspection {
                                              envvar_present: true,
                                              host: "beta.host".to_owned(),
                                              specs: vec![spec1.clone(), spec2.clone()],
+                                             health: HealthStatus::Healthy,
                                          }));
 
         // companion context
@@ -789,11 +1429,12 @@ mod tests {
         let mut ctx = Context::new(Arc::new(cfg),
                                    Box::new(inspector),
                                    Box::new(publisher.clone()),
+                                   Arc::new(TextReporter),
                                    term_recv);
         let mut errors = Vec::new();
 
         // #### WHEN  ####
-        refresh_container(Pending { todo: beta, explicit: true }, &mut errors, &mut ctx);
+        refresh_container(Pending { todo: beta, explicit: true }, &mut errors, &mut Vec::new(), &mut ctx);
 
         // #### THEN  ####
         assert!(errors.len() == 0, "Expected no errors, got {:#?}", errors);
@@ -816,6 +1457,83 @@ mod tests {
         }
     }
 
+    #[test]
+    fn refresh_skips_unhealthy_container_when_require_healthy() {
+        common::init_log();
+        // #### GIVEN ####
+        let mut cfg = Config::default();
+        cfg.require_healthy = true;
+
+        let publisher = Arc::new(RefCell::new(MockPublisher::default()));
+
+        let beta = Arc::new("beta".to_owned());
+        let mut inspector = MockInspector::default();
+        inspector.inspect_results.insert(beta.clone(),
+                                         Ok(Inspection {
+                                             envvar_present: true,
+                                             host: "beta.host".to_owned(),
+                                             specs: vec![DomainSpec::parse("beta.example.org:http").unwrap()],
+                                             health: HealthStatus::Unhealthy,
+                                         }));
+
+        let (term_send, term_recv) = chan::sync(1);
+        let mut ctx = Context::new(Arc::new(cfg),
+                                   Box::new(inspector),
+                                   Box::new(publisher.clone()),
+                                   Arc::new(TextReporter),
+                                   term_recv);
+        let mut errors = Vec::new();
+
+        // #### WHEN  ####
+        refresh_container(Pending { todo: beta, explicit: true }, &mut errors, &mut Vec::new(), &mut ctx);
+
+        // #### THEN  ####
+        assert!(errors.len() == 0, "An unhealthy container is skipped, not an error: {:#?}", errors);
+        let publisher_cell_ref = publisher.borrow();
+        let mock: &MockPublisher = publisher_cell_ref.deref();
+        assert!(mock.publications.is_empty(),
+                "Unhealthy container must not be published: {:#?}",
+                mock.publications);
+    }
+
+    #[test]
+    fn refresh_publishes_container_without_healthcheck_when_require_healthy() {
+        common::init_log();
+        // #### GIVEN ####
+        let mut cfg = Config::default();
+        cfg.require_healthy = true;
+
+        let publisher = Arc::new(RefCell::new(MockPublisher::default()));
+
+        let beta = Arc::new("beta".to_owned());
+        let mut inspector = MockInspector::default();
+        inspector.inspect_results.insert(beta.clone(),
+                                         Ok(Inspection {
+                                             envvar_present: true,
+                                             host: "beta.host".to_owned(),
+                                             specs: vec![DomainSpec::parse("beta.example.org:http").unwrap()],
+                                             health: HealthStatus::NoHealthcheck,
+                                         }));
+
+        let (term_send, term_recv) = chan::sync(1);
+        let mut ctx = Context::new(Arc::new(cfg),
+                                   Box::new(inspector),
+                                   Box::new(publisher.clone()),
+                                   Arc::new(TextReporter),
+                                   term_recv);
+        let mut errors = Vec::new();
+
+        // #### WHEN  ####
+        refresh_container(Pending { todo: beta, explicit: true }, &mut errors, &mut Vec::new(), &mut ctx);
+
+        // #### THEN  ####
+        assert!(errors.len() == 0, "Expected no errors, got {:#?}", errors);
+        let publisher_cell_ref = publisher.borrow();
+        let mock: &MockPublisher = publisher_cell_ref.deref();
+        assert!(!mock.publications.is_empty(),
+                "A container without a declared healthcheck has nothing to gate on.");
+    }
+
     #[test]
     fn refresh_dry_run() {
         common::init_log();
@@ -830,30 +1548,36 @@ mod tests {
                                                      Box::new(|| From::from(MockError))));
 
         // mock inspector
-        let alpha = Rc::new("alpha".to_owned());
-        let beta = Rc::new("beta".to_owned());
+        let alpha = Arc::new("alpha".to_owned());
+        let beta = Arc::new("beta".to_owned());
         let mut inspector = MockInspector::default();
         let spec1 = DomainSpec {
             domain_name: "one.alpha.domain".to_owned(),
             http_port: Some(80),
             https_port: Some(443),
+            http_external_port: None,
+            https_external_port: None,
         };
         let spec2 = DomainSpec {
             domain_name: "two.beta.domain".to_owned(),
             http_port: Some(8080),
             https_port: None,
+            http_external_port: None,
+            https_external_port: None,
         };
         inspector.inspect_results.insert(beta.clone(),
                                          Ok(Inspection {
                                              envvar_present: true,
                                              host: "beta.host".to_owned(),
                                              specs: vec![spec1.clone()],
+                                             health: HealthStatus::Healthy,
                                          }));
         inspector.inspect_results.insert(alpha.clone(),
                                          Ok(Inspection {
                                              envvar_present: true,
                                              host: "alpha.host".to_owned(),
                                              specs: vec![spec2.clone()],
+                                             health: HealthStatus::Healthy,
                                          }));
 
         // companion context
@@ -861,11 +1585,12 @@ mod tests {
         let mut ctx = Context::new(Arc::new(cfg),
                                    Box::new(inspector),
                                    Box::new(publisher.clone()),
+                                   Arc::new(TextReporter),
                                    term_recv);
         let mut errors = Vec::new();
 
         // #### WHEN  ####
-        refresh_container(Pending { todo: beta, explicit: true }, &mut errors, &mut ctx);
+        refresh_container(Pending { todo: beta, explicit: true }, &mut errors, &mut Vec::new(), &mut ctx);
 
         // #### THEN  ####
         assert!(errors.len() == 0, "Expected no errors, got {:#?}", errors);
@@ -887,30 +1612,36 @@ mod tests {
                                                      Box::new(|| From::from(MockError))));
 
         // mock inspector
-        let alpha = Rc::new("alpha".to_owned());
-        let beta = Rc::new("beta".to_owned());
+        let alpha = Arc::new("alpha".to_owned());
+        let beta = Arc::new("beta".to_owned());
         let mut inspector = MockInspector::default();
         let spec1 = DomainSpec {
             domain_name: "one.alpha.domain".to_owned(),
             http_port: Some(80),
             https_port: Some(443),
+            http_external_port: None,
+            https_external_port: None,
         };
         let spec2 = DomainSpec {
             domain_name: "two.beta.domain".to_owned(),
             http_port: Some(8080),
             https_port: None,
+            http_external_port: None,
+            https_external_port: None,
         };
         inspector.inspect_results.insert(beta.clone(),
                                          Ok(Inspection {
                                              envvar_present: true,
                                              host: "beta.host".to_owned(),
                                              specs: vec![spec1.clone()],
+                                             health: HealthStatus::Healthy,
                                          }));
         inspector.inspect_results.insert(alpha.clone(),
                                          Ok(Inspection {
                                              envvar_present: true,
                                              host: "alpha.host".to_owned(),
                                              specs: vec![spec2.clone()],
+                                             health: HealthStatus::Healthy,
                                          }));
 
         // companion context
@@ -918,11 +1649,12 @@ mod tests {
         let mut ctx = Context::new(Arc::new(cfg),
                                    Box::new(inspector),
                                    Box::new(publisher.clone()),
+                                   Arc::new(TextReporter),
                                    term_recv);
         let mut errors = Vec::new();
 
         // #### WHEN  ####
-        refresh_container(Pending { todo: beta, explicit: true }, &mut errors, &mut ctx);
+        refresh_container(Pending { todo: beta, explicit: true }, &mut errors, &mut Vec::new(), &mut ctx);
 
         // #### THEN  ####
         assert!(errors.len() > 0, "Expected some errors, got {:#?}", errors);
@@ -944,30 +1676,36 @@ mod tests {
         let publisher = Arc::new(RefCell::new(MockPublisher::default()));
 
         // mock inspector
-        let alpha = Rc::new("alpha".to_owned());
-        let beta = Rc::new("beta".to_owned());
+        let alpha = Arc::new("alpha".to_owned());
+        let beta = Arc::new("beta".to_owned());
         let mut inspector = MockInspector::default();
         let spec1 = DomainSpec {
             domain_name: "one.alpha.domain".to_owned(),
             http_port: Some(80),
             https_port: Some(443),
+            http_external_port: None,
+            https_external_port: None,
         };
         let spec2 = DomainSpec {
             domain_name: "two.beta.domain".to_owned(),
             http_port: Some(8080),
             https_port: None,
+            http_external_port: None,
+            https_external_port: None,
         };
         inspector.inspect_results.insert(beta.clone(),
                                          Ok(Inspection {
                                              envvar_present: false,
                                              host: "beta.host".to_owned(),
                                              specs: Vec::new(),
+                                             health: HealthStatus::Healthy,
                                          }));
         inspector.inspect_results.insert(alpha.clone(),
                                          Ok(Inspection {
                                              envvar_present: true,
                                              host: "alpha.host".to_owned(),
                                              specs: vec![spec2.clone()],
+                                             health: HealthStatus::Healthy,
                                          }));
 
         // companion context
@@ -975,11 +1713,12 @@ mod tests {
         let mut ctx = Context::new(Arc::new(cfg),
                                    Box::new(inspector),
                                    Box::new(publisher.clone()),
+                                   Arc::new(TextReporter),
                                    term_recv);
         let mut errors = Vec::new();
 
         // #### WHEN  ####
-        refresh_container(Pending { todo: beta, explicit: true }, &mut errors, &mut ctx);
+        refresh_container(Pending { todo: beta, explicit: true }, &mut errors, &mut Vec::new(), &mut ctx);
 
         // #### THEN  ####
         assert!(errors.len() > 0, "Expected some errors, got {:#?}", errors);
@@ -1001,30 +1740,36 @@ mod tests {
         let publisher = Arc::new(RefCell::new(MockPublisher::default()));
 
         // mock inspector
-        let alpha = Rc::new("alpha".to_owned());
-        let beta = Rc::new("beta".to_owned());
+        let alpha = Arc::new("alpha".to_owned());
+        let beta = Arc::new("beta".to_owned());
         let mut inspector = MockInspector::default();
         let spec1 = DomainSpec {
             domain_name: "one.alpha.domain".to_owned(),
             http_port: Some(80),
             https_port: Some(443),
+            http_external_port: None,
+            https_external_port: None,
         };
         let spec2 = DomainSpec {
             domain_name: "two.beta.domain".to_owned(),
             http_port: Some(8080),
             https_port: None,
+            http_external_port: None,
+            https_external_port: None,
         };
         inspector.inspect_results.insert(beta.clone(),
                                          Ok(Inspection {
                                              envvar_present: false,
                                              host: "beta.host".to_owned(),
                                              specs: Vec::new(),
+                                             health: HealthStatus::Healthy,
                                          }));
         inspector.inspect_results.insert(alpha.clone(),
                                          Ok(Inspection {
                                              envvar_present: true,
                                              host: "alpha.host".to_owned(),
                                              specs: vec![spec2.clone()],
+                                             health: HealthStatus::Healthy,
                                          }));
 
         // companion context
@@ -1032,11 +1777,12 @@ mod tests {
         let mut ctx = Context::new(Arc::new(cfg),
                                    Box::new(inspector),
                                    Box::new(publisher.clone()),
+                                   Arc::new(TextReporter),
                                    term_recv);
         let mut errors = Vec::new();
 
         // #### WHEN  ####
-        refresh_container(Pending { todo: beta, explicit: false }, &mut errors, &mut ctx);
+        refresh_container(Pending { todo: beta, explicit: false }, &mut errors, &mut Vec::new(), &mut ctx);
 
         // #### THEN  ####
         // This time, the inspection error shouldn't be treated as something serious
@@ -1058,30 +1804,36 @@ mod tests {
         let publisher = Arc::new(RefCell::new(MockPublisher::default()));
 
         // mock inspector
-        let alpha = Rc::new("alpha".to_owned());
-        let beta = Rc::new("beta".to_owned());
+        let alpha = Arc::new("alpha".to_owned());
+        let beta = Arc::new("beta".to_owned());
         let mut inspector = MockInspector::default();
         let spec1 = DomainSpec {
             domain_name: "one.alpha.domain".to_owned(),
             http_port: Some(80),
             https_port: Some(443),
+            http_external_port: None,
+            https_external_port: None,
         };
         let spec2 = DomainSpec {
             domain_name: "two.beta.domain".to_owned(),
             http_port: Some(8080),
             https_port: None,
+            http_external_port: None,
+            https_external_port: None,
         };
         inspector.inspect_results.insert(beta.clone(),
                                          Ok(Inspection {
                                              envvar_present: false,
                                              host: "beta.host".to_owned(),
                                              specs: Vec::new(),
+                                             health: HealthStatus::Healthy,
                                          }));
         inspector.inspect_results.insert(alpha.clone(),
                                          Ok(Inspection {
                                              envvar_present: true,
                                              host: "alpha.host".to_owned(),
                                              specs: vec![spec2.clone()],
+                                             health: HealthStatus::Healthy,
                                          }));
 
         // companion context
@@ -1089,11 +1841,12 @@ mod tests {
         let mut ctx = Context::new(Arc::new(cfg),
                                    Box::new(inspector),
                                    Box::new(publisher.clone()),
+                                   Arc::new(TextReporter),
                                    term_recv);
         let mut errors = Vec::new();
 
         // #### WHEN  ####
-        refresh_container(Pending { todo: beta, explicit: true }, &mut errors, &mut ctx);
+        refresh_container(Pending { todo: beta, explicit: true }, &mut errors, &mut Vec::new(), &mut ctx);
 
         // #### THEN  ####
         // This time, the inspection error shouldn't be treated as something serious
@@ -1115,18 +1868,22 @@ mod tests {
         let publisher = Arc::new(RefCell::new(MockPublisher::default()));
 
         // mock inspector
-        let alpha = Rc::new("alpha".to_owned());
-        let beta = Rc::new("beta".to_owned());
+        let alpha = Arc::new("alpha".to_owned());
+        let beta = Arc::new("beta".to_owned());
         let mut inspector = MockInspector::default();
         let spec1 = DomainSpec {
             domain_name: "one.alpha.domain".to_owned(),
             http_port: Some(80),
             https_port: Some(443),
+            http_external_port: None,
+            https_external_port: None,
         };
         let spec2 = DomainSpec {
             domain_name: "two.beta.domain".to_owned(),
             http_port: Some(8080),
             https_port: None,
+            http_external_port: None,
+            https_external_port: None,
         };
         inspector.inspect_results.insert(beta.clone(), Err(Box::new(|| From::from(FakeError))));
         inspector.inspect_results.insert(alpha.clone(),
@@ -1134,6 +1891,7 @@ mod tests {
                                              envvar_present: true,
                                              host: "alpha.host".to_owned(),
                                              specs: vec![spec2.clone()],
+                                             health: HealthStatus::Healthy,
                                          }));
 
         // companion context
@@ -1141,11 +1899,12 @@ mod tests {
         let mut ctx = Context::new(Arc::new(cfg),
                                    Box::new(inspector),
                                    Box::new(publisher.clone()),
+                                   Arc::new(TextReporter),
                                    term_recv);
         let mut errors = Vec::new();
 
         // #### WHEN  ####
-        refresh_container(Pending { todo: beta, explicit: false }, &mut errors, &mut ctx);
+        refresh_container(Pending { todo: beta, explicit: false }, &mut errors, &mut Vec::new(), &mut ctx);
 
         // #### THEN  ####
         assert!(errors.len() > 0, "Expected some errors, got {:#?}", errors);
@@ -1167,18 +1926,22 @@ mod tests {
         let publisher = Arc::new(RefCell::new(MockPublisher::default()));
 
         // mock inspector
-        let alpha = Rc::new("alpha".to_owned());
-        let beta = Rc::new("beta".to_owned());
+        let alpha = Arc::new("alpha".to_owned());
+        let beta = Arc::new("beta".to_owned());
         let mut inspector = MockInspector::default();
         let spec1 = DomainSpec {
             domain_name: "one.alpha.domain".to_owned(),
             http_port: Some(80),
             https_port: Some(443),
+            http_external_port: None,
+            https_external_port: None,
         };
         let spec2 = DomainSpec {
             domain_name: "two.beta.domain".to_owned(),
             http_port: Some(8080),
             https_port: None,
+            http_external_port: None,
+            https_external_port: None,
         };
         inspector.inspect_results.insert(beta.clone(), Err(Box::new(|| From::from(FakeError))));
         inspector.inspect_results.insert(alpha.clone(),
@@ -1186,6 +1949,7 @@ mod tests {
                                              envvar_present: true,
                                              host: "alpha.host".to_owned(),
                                              specs: vec![spec2.clone()],
+                                             health: HealthStatus::Healthy,
                                          }));
 
         // companion context
@@ -1193,11 +1957,12 @@ mod tests {
         let mut ctx = Context::new(Arc::new(cfg),
                                    Box::new(inspector),
                                    Box::new(publisher.clone()),
+                                   Arc::new(TextReporter),
                                    term_recv);
         let mut errors = Vec::new();
 
         // #### WHEN  ####
-        refresh_container(Pending { todo: beta, explicit: false }, &mut errors, &mut ctx);
+        refresh_container(Pending { todo: beta, explicit: false }, &mut errors, &mut Vec::new(), &mut ctx);
 
         // #### THEN  ####
         assert!(errors.len() == 0, "Expected no errors, got {:#?}", errors);
@@ -1206,13 +1971,407 @@ mod tests {
                 publisher.borrow().publications);
     }
 
-    /// Normally, DomainSpec isn't directly comparable because instances might not be in canonical
-    /// form, but for testing, this is good enough.
-    impl PartialEq for DomainSpec {
-        fn eq(&self, other: &DomainSpec) -> bool {
-            self.domain_name == other.domain_name && self.http_port == other.http_port &&
-            self.https_port == other.https_port
+    #[test]
+    fn reconcile_unpublishes_stale_host() {
+        common::init_log();
+        // #### GIVEN ####
+        let cfg = Config::default();
+        let publisher = Arc::new(RefCell::new(MockPublisher::default()));
+        let mut inspector = MockInspector::default();
+        let alpha = Arc::new("alpha".to_owned());
+        let spec1 = DomainSpec {
+            domain_name: "one.alpha.domain".to_owned(),
+            http_port: Some(80),
+            https_port: Some(443),
+            http_external_port: None,
+            https_external_port: None,
+        };
+        inspector.inspect_results.insert(alpha.clone(),
+                                         Ok(Inspection {
+                                             envvar_present: true,
+                                             host: "alpha.host".to_owned(),
+                                             specs: vec![spec1.clone()],
+                                             health: HealthStatus::Healthy,
+                                         }));
+
+        let (term_send, term_recv) = chan::sync(1);
+        let mut ctx = Context::new(Arc::new(cfg),
+                                   Box::new(inspector),
+                                   Box::new(publisher.clone()),
+                                   Arc::new(TextReporter),
+                                   term_recv);
+        let mut errors = Vec::new();
+
+        // #### WHEN  ####
+        // First cycle: alpha gets published.
+        let mut published = Vec::new();
+        refresh_container(Pending { todo: alpha.clone(), explicit: true },
+                          &mut errors,
+                          &mut published,
+                          &mut ctx);
+        ctx.reconcile(published);
+
+        // Second cycle: alpha's container is gone, so nothing gets refreshed/published.
+        ctx.reconcile(Vec::new());
+
+        // #### THEN  ####
+        assert_eq!(publisher.borrow().publications.len(), 1);
+        assert_eq!(publisher.borrow().unpublications.len(), 1);
+        assert_eq!(publisher.borrow().unpublications[0].0, "alpha.host");
+        assert_eq!(publisher.borrow().unpublications[0].1, vec![spec1]);
+    }
+
+    #[test]
+    fn reconcile_dry_run_only_reports_would_be_unpublishes() {
+        common::init_log();
+        // #### GIVEN ####
+        let mut cfg = Config::default();
+        cfg.dry_run = true;
+        let publisher = Arc::new(RefCell::new(MockPublisher::default()));
+        let mut inspector = MockInspector::default();
+        let alpha = Arc::new("alpha".to_owned());
+        let spec1 = DomainSpec {
+            domain_name: "one.alpha.domain".to_owned(),
+            http_port: Some(80),
+            https_port: Some(443),
+            http_external_port: None,
+            https_external_port: None,
+        };
+        inspector.inspect_results.insert(alpha.clone(),
+                                         Ok(Inspection {
+                                             envvar_present: true,
+                                             host: "alpha.host".to_owned(),
+                                             specs: vec![spec1.clone()],
+                                             health: HealthStatus::Healthy,
+                                         }));
+
+        let (term_send, term_recv) = chan::sync(1);
+        let mut ctx = Context::new(Arc::new(cfg),
+                                   Box::new(inspector),
+                                   Box::new(publisher.clone()),
+                                   Arc::new(TextReporter),
+                                   term_recv);
+        let mut errors = Vec::new();
+
+        // #### WHEN  ####
+        let mut published = Vec::new();
+        refresh_container(Pending { todo: alpha.clone(), explicit: true },
+                          &mut errors,
+                          &mut published,
+                          &mut ctx);
+        ctx.reconcile(published);
+        ctx.reconcile(Vec::new());
+
+        // #### THEN  ####
+        assert_eq!(publisher.borrow().publications.len(), 0, "dry run must not publish");
+        assert_eq!(publisher.borrow().unpublications.len(), 0, "dry run must not unpublish");
+    }
+
+    #[test]
+    fn die_event_unpublishes_immediately() {
+        common::init_log();
+        // #### GIVEN ####
+        let cfg = Config::default();
+        let publisher = Arc::new(RefCell::new(MockPublisher::default()));
+        let mut inspector = MockInspector::default();
+        let beta = Arc::new("beta".to_owned());
+        let spec1 = DomainSpec {
+            domain_name: "one.beta.domain".to_owned(),
+            http_port: Some(80),
+            https_port: Some(443),
+            http_external_port: None,
+            https_external_port: None,
+        };
+        inspector.inspect_results.insert(beta.clone(),
+                                         Ok(Inspection {
+                                             envvar_present: true,
+                                             host: "beta.host".to_owned(),
+                                             specs: vec![spec1.clone()],
+                                             health: HealthStatus::Healthy,
+                                         }));
+
+        let (term_send, term_recv) = chan::sync(1);
+        let mut ctx = Context::new(Arc::new(cfg),
+                                   Box::new(inspector),
+                                   Box::new(publisher.clone()),
+                                   Arc::new(TextReporter),
+                                   term_recv);
+        let mut errors = Vec::new();
+
+        // #### WHEN  ####
+        // Event-driven publish (no full cycle/reconcile involved), then the container dies.
+        refresh_from_event(ContainerEvent { container_name: beta.clone(), kind: ContainerEventKind::Start },
+                           &[],
+                           &mut errors,
+                           &mut ctx);
+        refresh_from_event(ContainerEvent { container_name: beta.clone(), kind: ContainerEventKind::Die },
+                           &[],
+                           &mut errors,
+                           &mut ctx);
+
+        // #### THEN  ####
+        assert_eq!(errors.len(), 0, "errors: {:#?}", errors);
+        assert_eq!(publisher.borrow().publications.len(), 1);
+        assert_eq!(publisher.borrow().unpublications.len(), 1,
+                   "die must retract the publication immediately, not wait for its TTL");
+        assert_eq!(publisher.borrow().unpublications[0].0, "beta.host");
+        assert_eq!(publisher.borrow().unpublications[0].1, vec![spec1]);
+    }
+
+    #[test]
+    fn die_event_for_unknown_container_is_a_noop() {
+        common::init_log();
+        // #### GIVEN ####
+        let cfg = Config::default();
+        let publisher = Arc::new(RefCell::new(MockPublisher::default()));
+        let inspector = MockInspector::default();
+        let beta = Arc::new("beta".to_owned());
+
+        let (term_send, term_recv) = chan::sync(1);
+        let mut ctx = Context::new(Arc::new(cfg),
+                                   Box::new(inspector),
+                                   Box::new(publisher.clone()),
+                                   Arc::new(TextReporter),
+                                   term_recv);
+        let mut errors = Vec::new();
+
+        // #### WHEN  ####
+        // No prior publication is known for this container (e.g. it never matched the envvar).
+        refresh_from_event(ContainerEvent { container_name: beta, kind: ContainerEventKind::Destroy },
+                           &[],
+                           &mut errors,
+                           &mut ctx);
+
+        // #### THEN  ####
+        assert_eq!(errors.len(), 0);
+        assert_eq!(publisher.borrow().unpublications.len(), 0);
+    }
+
+    #[test]
+    fn watch_event_die_unpublishes_immediately() {
+        common::init_log();
+        // #### GIVEN ####
+        let (term_send, term_recv) = chan::sync(1);
+        let mut cfg = Config::default();
+        cfg.refresh_seconds = Some(60);
+        cfg.watch = true;
+        let (event_send, event_recv) = chan::async();
+        let mut inspector = MockInspector::default();
+        inspector.watch_events = Some(event_recv);
+        let beta = Arc::new("beta".to_owned());
+        let spec1 = DomainSpec {
+            domain_name: "one.beta.domain".to_owned(),
+            http_port: Some(80),
+            https_port: Some(443),
+            http_external_port: None,
+            https_external_port: None,
+        };
+        inspector.inspect_results.insert(beta.clone(),
+                                         Ok(Inspection {
+                                             envvar_present: true,
+                                             host: "beta.host".to_owned(),
+                                             specs: vec![spec1.clone()],
+                                             health: HealthStatus::Healthy,
+                                         }));
+        let publisher = Arc::new(RefCell::new(MockPublisher::default()));
+        let mut ctx = Context::new(Arc::new(cfg),
+                                   Box::new(inspector),
+                                   Box::new(publisher.clone()),
+                                   Arc::new(TextReporter),
+                                   term_recv);
+        let mut errors = Vec::new();
+
+        // #### WHEN  ####
+        // Exercise the same path `run` does: `Context::wait` picks the event batch off the
+        // `--watch` channel, and each event is handed to `refresh_from_event`.
+        event_send.send(ContainerEvent { container_name: beta.clone(), kind: ContainerEventKind::Start });
+        match ctx.wait() {
+            Wake::Events(events) => {
+                for event in events {
+                    refresh_from_event(event, &[], &mut errors, &mut ctx);
+                }
+            }
+            _ => assert!(false, "Expected Wake::Events, got something else."),
+        }
+        event_send.send(ContainerEvent { container_name: beta.clone(), kind: ContainerEventKind::Die });
+        match ctx.wait() {
+            Wake::Events(events) => {
+                for event in events {
+                    refresh_from_event(event, &[], &mut errors, &mut ctx);
+                }
+            }
+            _ => assert!(false, "Expected Wake::Events, got something else."),
         }
+
+        // #### THEN  ####
+        assert_eq!(errors.len(), 0, "errors: {:#?}", errors);
+        assert_eq!(publisher.borrow().publications.len(), 1);
+        assert_eq!(publisher.borrow().unpublications.len(), 1,
+                   "a die event delivered through the watch channel must unpublish immediately, \
+                    not just one passed directly to refresh_from_event");
+    }
+
+    #[test]
+    fn backoff_delay_respects_cap() {
+        let mut rng = XorShiftRng::new(42);
+        for attempt in 0..10 {
+            let delay = backoff_delay(attempt, 200, 5_000, &mut rng);
+            assert!(delay <= Duration::from_millis(5_000),
+                    "attempt {} produced delay {:?} above the cap",
+                    attempt,
+                    delay);
+        }
+    }
+
+    #[test]
+    fn backoff_delay_is_deterministic_given_a_seed() {
+        let mut rng_a = XorShiftRng::new(1234);
+        let mut rng_b = XorShiftRng::new(1234);
+        let delays_a: Vec<Duration> = (0..5).map(|a| backoff_delay(a, 100, 10_000, &mut rng_a)).collect();
+        let delays_b: Vec<Duration> = (0..5).map(|a| backoff_delay(a, 100, 10_000, &mut rng_b)).collect();
+        assert_eq!(delays_a, delays_b);
+    }
+
+    #[test]
+    fn to_publication_collapses_equivalent_specs() {
+        let explicit = DomainSpec {
+            domain_name: "Example.org.".to_owned(),
+            http_port: Some(80),
+            https_port: Some(443),
+            http_external_port: None,
+            https_external_port: None,
+        };
+        let implicit = DomainSpec::parse("example.org").unwrap();
+        let inspection = Pending {
+            explicit: true,
+            todo: Inspection {
+                envvar_present: true,
+                host: "example.host".to_owned(),
+                specs: vec![explicit, implicit],
+                health: HealthStatus::Healthy,
+            },
+        };
+
+        let publication = to_publication(inspection);
+
+        assert_eq!(publication.specs.len(), 1, "equivalent specs must collapse into one");
+    }
+
+    #[test]
+    fn to_publication_backfills_omitted_external_ports() {
+        let spec = DomainSpec::parse("app.example.org:http=8080").unwrap();
+        let inspection = Pending {
+            explicit: true,
+            todo: Inspection {
+                envvar_present: true,
+                host: "app.host".to_owned(),
+                specs: vec![spec],
+                health: HealthStatus::Healthy,
+            },
+        };
+
+        let publication = to_publication(inspection);
+
+        assert_eq!(publication.specs[0].http_external_port, Some(8080));
+    }
+
+    #[test]
+    fn ensure_acme_certificates_loads_provisioned_cert_into_store() {
+        common::init_log();
+        let mut cfg = Config::default();
+        cfg.acme_enabled = true;
+        let mut ctx = Context::new(Arc::new(cfg),
+                                   Box::new(MockInspector::default()),
+                                   Box::new(MockPublisher::default()),
+                                   Arc::new(TextReporter),
+                                   chan::sync(1).1);
+        ctx.acme = Some(AcmeManager::new(Box::new(MockTransport::new_valid("secure.example.org")),
+                                         Box::new(InMemoryChallengeResponder::default()),
+                                         30));
+
+        let publication = Publication {
+            host: "secure.host".to_owned(),
+            specs: vec![DomainSpec {
+                            domain_name: "secure.example.org".to_owned(),
+                            http_port: None,
+                            https_port: Some(443),
+                            http_external_port: None,
+                            https_external_port: None,
+                        }],
+        };
+        let mut errors = Vec::new();
+
+        ctx.ensure_acme_certificates(&publication, &mut errors);
+
+        assert_eq!(errors.len(), 0, "expected no errors, got {:#?}", errors);
+        assert!(ctx.cert_store.resolve("secure.example.org").is_some());
+    }
+
+    #[test]
+    fn ensure_acme_certificates_reports_missing_certificate() {
+        common::init_log();
+        let mut cfg = Config::default();
+        cfg.acme_enabled = true;
+        let mut ctx = Context::new(Arc::new(cfg),
+                                   Box::new(MockInspector::default()),
+                                   Box::new(MockPublisher::default()),
+                                   Arc::new(TextReporter),
+                                   chan::sync(1).1);
+        ctx.acme = Some(AcmeManager::new(Box::new(MockTransport::new_failing("broken.example.org")),
+                                         Box::new(InMemoryChallengeResponder::default()),
+                                         30));
+
+        let publication = Publication {
+            host: "broken.host".to_owned(),
+            specs: vec![DomainSpec {
+                            domain_name: "broken.example.org".to_owned(),
+                            http_port: None,
+                            https_port: Some(443),
+                            http_external_port: None,
+                            https_external_port: None,
+                        }],
+        };
+        let mut errors = Vec::new();
+
+        ctx.ensure_acme_certificates(&publication, &mut errors);
+
+        assert_eq!(errors.len(), 1, "expected a missing-certificate error, got {:#?}", errors);
+        assert!(ctx.cert_store.resolve("broken.example.org").is_none());
+    }
+
+    #[test]
+    fn ensure_acme_certificates_skips_ip_literal_hosts() {
+        common::init_log();
+        let mut cfg = Config::default();
+        cfg.acme_enabled = true;
+        let mut ctx = Context::new(Arc::new(cfg),
+                                   Box::new(MockInspector::default()),
+                                   Box::new(MockPublisher::default()),
+                                   Arc::new(TextReporter),
+                                   chan::sync(1).1);
+        let transport = MockTransport::new_valid("10.0.0.1");
+        ctx.acme = Some(AcmeManager::new(Box::new(transport),
+                                         Box::new(InMemoryChallengeResponder::default()),
+                                         30));
+
+        let publication = Publication {
+            host: "fixed-ip.host".to_owned(),
+            specs: vec![DomainSpec {
+                            domain_name: "10.0.0.1".to_owned(),
+                            http_port: None,
+                            https_port: Some(443),
+                            http_external_port: None,
+                            https_external_port: None,
+                        }],
+        };
+        let mut errors = Vec::new();
+
+        ctx.ensure_acme_certificates(&publication, &mut errors);
+
+        assert_eq!(errors.len(),
+                   0,
+                   "an IP-literal host has nothing ACME could provision, so it shouldn't be reported as an error: {:#?}",
+                   errors);
+        assert!(ctx.cert_store.resolve("10.0.0.1").is_none());
     }
-    impl Eq for DomainSpec {}
 }
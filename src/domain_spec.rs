@@ -0,0 +1,545 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Christian Klauser
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use regex::Regex;
+use url::idna;
+
+quick_error! {
+    #[derive(Debug)]
+    pub enum DomainSpecError {
+        InvalidFormat(raw: String) {
+            description("Domain-spec has an invalid format.")
+            display(me) -> ("{} raw value: {:?}", me.description(), raw)
+        }
+        InvalidPort(raw: String, err: ::std::num::ParseIntError) {
+            description("Domain-spec port is not a valid port number.")
+            cause(err)
+            display(me) -> ("{} raw value: {:?}, error: {}", me.description(), raw, err)
+        }
+    }
+}
+
+/// The host part of a [DomainSpec], recognized as one of three forms: a DNS name, an IPv4
+/// literal, or a bracketed IPv6 literal (`[::1]`, bracketed per RFC 3986 so its colons aren't
+/// confused with a port separator). Parsing never fails: anything that isn't a valid IP literal
+/// is treated as a DNS name, same as a browser address bar would.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Host {
+    Dns(String),
+    Ip(IpAddr),
+}
+
+impl Host {
+    fn parse(raw: &str) -> Host {
+        if raw.starts_with('[') && raw.ends_with(']') {
+            if let Ok(addr) = raw[1..raw.len() - 1].parse::<Ipv6Addr>() {
+                return Host::Ip(IpAddr::V6(addr));
+            }
+        } else if let Ok(addr) = raw.parse::<Ipv4Addr>() {
+            return Host::Ip(IpAddr::V4(addr));
+        }
+        Host::Dns(raw.to_owned())
+    }
+}
+
+/// A [Host] together with an optional port, e.g. as used to tell `example.com` apart from
+/// `example.com:8080` when matching a target rather than just a bare domain (see
+/// `cert_store::CertStore`). Recognizes the same five forms auth-domain matching typically does:
+/// `host`, `host:port`, `ip`, `ip:port`, and `[ipv6]:port`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct HostPort {
+    pub host: Host,
+    pub port: Option<u16>,
+}
+
+impl HostPort {
+    /// Splits a trailing `:PORT` off of `raw`, treating a bracketed IPv6 literal's own colons as
+    /// part of the host rather than a port separator. If what follows the last `:` doesn't parse
+    /// as a `u16`, it's assumed to be part of the host instead of a malformed port (e.g. a DNS
+    /// name that happens to contain a colon).
+    pub fn parse(raw: &str) -> HostPort {
+        if raw.starts_with('[') {
+            if let Some(end) = raw.find(']') {
+                let host = Host::parse(&raw[..end + 1]);
+                let port = if raw[end + 1..].starts_with(':') {
+                    raw[end + 2..].parse::<u16>().ok()
+                } else {
+                    None
+                };
+                return HostPort { host: host, port: port };
+            }
+        }
+        match raw.rfind(':') {
+            Some(idx) => {
+                match raw[idx + 1..].parse::<u16>() {
+                    Ok(port) => HostPort { host: Host::parse(&raw[..idx]), port: Some(port) },
+                    Err(_) => HostPort { host: Host::parse(raw), port: None },
+                }
+            }
+            None => HostPort { host: Host::parse(raw), port: None },
+        }
+    }
+}
+
+/// A single domain a container wants to be reachable under, together with the ports it expects
+/// HTTP and/or HTTPS traffic to be routed to, and (optionally) the different port the reverse
+/// proxy should advertise those under externally (see [DomainSpec::effective_http_external_port]).
+///
+/// Parsed from one space-separated token of the `BEACHHEAD_DOMAINS` (or configured) environment
+/// variable using the grammar `DOMAIN[:http[=PORT[@EXTERNAL]]][:https[=PORT[@EXTERNAL]]]` (see
+/// USAGE in `main`).
+///
+/// Not directly comparable: two specs can mean the same thing without being field-for-field
+/// identical (different case, a trailing dot, a non-ASCII label, an explicit default port, an
+/// external port spelled out that just repeats the internal one). Equality, hashing and ordering
+/// are instead derived from [DomainSpec::canonical]'s identity key (the same approach rust-url
+/// uses for `RelativeSchemeData`), so specs can be deduplicated via `HashSet`/`BTreeMap` without
+/// callers having to canonicalize them first.
+#[derive(Debug, Clone, RustcEncodable, RustcDecodable)]
+pub struct DomainSpec {
+    pub domain_name: String,
+    pub http_port: Option<u16>,
+    pub https_port: Option<u16>,
+    /// The port HTTP should be advertised under externally, if different from `http_port`.
+    /// `None` means "advertise under `http_port` itself" (see
+    /// [DomainSpec::effective_http_external_port]).
+    pub http_external_port: Option<u16>,
+    /// The port HTTPS should be advertised under externally, if different from `https_port`.
+    /// `None` means "advertise under `https_port` itself" (see
+    /// [DomainSpec::effective_https_external_port]).
+    pub https_external_port: Option<u16>,
+}
+
+const DEFAULT_HTTP_PORT: u16 = 80;
+const DEFAULT_HTTPS_PORT: u16 = 443;
+
+lazy_static! {
+    // The domain group accepts a bracketed IPv6 literal (`[::1]`) as a whole, since its colons
+    // would otherwise be indistinguishable from the `:http`/`:https` scheme separators.
+    static ref SPEC_RE: Regex =
+        Regex::new(r"^(?P<domain>\[[^\]]+\]|[^:]+)(?P<schemes>(?::(?:http|https)(?:=\d+(?:@\d+)?)?)*)$")
+            .expect("DomainSpec spec regex must compile");
+    static ref SCHEME_RE: Regex =
+        Regex::new(r":(?P<scheme>http|https)(?:=(?P<port>\d+)(?:@(?P<external>\d+))?)?")
+            .expect("DomainSpec scheme regex must compile");
+}
+
+impl DomainSpec {
+    /// Parses a single token of the `BEACHHEAD_DOMAINS` grammar. If neither `http` nor `https`
+    /// is mentioned, both are assumed with their default ports (80/443 respectively). An
+    /// `=PORT` may be followed by `@EXTERNAL` to advertise that port under a different,
+    /// externally visible one (see [DomainSpec::effective_http_external_port]); omitting it
+    /// (or the whole scheme) leaves the external port unset.
+    pub fn parse(raw: &str) -> Result<DomainSpec, DomainSpecError> {
+        let captures = match SPEC_RE.captures(raw) {
+            Some(c) => c,
+            None => return Err(DomainSpecError::InvalidFormat(raw.to_owned())),
+        };
+        let domain_name = captures.name("domain").unwrap().to_owned();
+        let schemes = captures.name("schemes").unwrap_or("");
+
+        let mut http_port = None;
+        let mut https_port = None;
+        let mut http_external_port = None;
+        let mut https_external_port = None;
+        let mut any_scheme = false;
+        for scheme_capture in SCHEME_RE.captures_iter(schemes) {
+            any_scheme = true;
+            let scheme = scheme_capture.name("scheme").unwrap();
+            let port = match scheme_capture.name("port") {
+                Some(p) => {
+                    Some(try!(p.parse::<u16>()
+                        .map_err(|e| DomainSpecError::InvalidPort(raw.to_owned(), e))))
+                }
+                None if scheme == "http" => Some(DEFAULT_HTTP_PORT),
+                None => Some(DEFAULT_HTTPS_PORT),
+            };
+            let external_port = match scheme_capture.name("external") {
+                Some(p) => {
+                    Some(try!(p.parse::<u16>()
+                        .map_err(|e| DomainSpecError::InvalidPort(raw.to_owned(), e))))
+                }
+                None => None,
+            };
+            if scheme == "http" {
+                http_port = port;
+                http_external_port = external_port;
+            } else {
+                https_port = port;
+                https_external_port = external_port;
+            }
+        }
+
+        if !any_scheme {
+            http_port = Some(DEFAULT_HTTP_PORT);
+            https_port = Some(DEFAULT_HTTPS_PORT);
+        }
+
+        Ok(DomainSpec {
+            domain_name: domain_name,
+            http_port: http_port,
+            https_port: https_port,
+            http_external_port: http_external_port,
+            https_external_port: https_external_port,
+        })
+    }
+
+    /// Parses [Host::Dns]/[Host::Ip] out of `domain_name`. Useful for callers (the inspector and
+    /// publisher) that need to treat containers reachable by IP differently from ones reachable
+    /// by DNS name, e.g. to skip DNS-only provisioning steps like ACME http-01 for an IP literal.
+    pub fn host(&self) -> Host {
+        Host::parse(&self.domain_name)
+    }
+
+    /// Returns a normalized copy of this spec. For a DNS name: lowercased, a trailing dot is
+    /// stripped, a leading dot (`.example.com`, sometimes used to mean "this name or any
+    /// subdomain") is normalized away to the bare name, and any non-ASCII labels are
+    /// IDNA/punycode-encoded. A leading wildcard label (`*.example.com`, see
+    /// `::cert_store::CertStore`) is left alone; only the rest of the name is IDNA-encoded. An IP
+    /// literal is left as-is apart from lowercasing (relevant for IPv6's hex digits) — punycode
+    /// encoding and wildcard/leading-dot handling don't apply to it. Port fields are left as-is,
+    /// since `parse` already collapses "scheme omitted entirely" and "scheme given without a
+    /// port" to the same default (`Some(80)`/`Some(443)`) — the only remaining distinct case,
+    /// `None`, means "this scheme isn't wanted" and must stay distinguishable.
+    pub fn canonical(&self) -> DomainSpec {
+        let mut domain_name = self.domain_name.to_lowercase();
+        if domain_name.ends_with('.') {
+            domain_name.pop();
+        }
+
+        let domain_name = match Host::parse(&domain_name) {
+            Host::Ip(_) => domain_name,
+            Host::Dns(name) => {
+                let name = if name.starts_with('.') {
+                    name[1..].to_owned()
+                } else {
+                    name
+                };
+                let (prefix, rest) = if name.starts_with("*.") {
+                    (&name[..2], &name[2..])
+                } else {
+                    ("", name.as_str())
+                };
+                let encoded = idna::domain_to_ascii(rest).unwrap_or_else(|_| rest.to_owned());
+                format!("{}{}", prefix, encoded)
+            }
+        };
+
+        DomainSpec {
+            domain_name: domain_name,
+            http_port: self.http_port,
+            https_port: self.https_port,
+            http_external_port: self.http_external_port,
+            https_external_port: self.https_external_port,
+        }
+    }
+
+    /// The port HTTP should be advertised under externally: `http_external_port` if set,
+    /// otherwise `http_port` itself (the common case of advertising under the container's own
+    /// port). `None` if `http_port` is `None` (HTTP isn't wanted for this spec at all).
+    pub fn effective_http_external_port(&self) -> Option<u16> {
+        self.http_port.map(|port| self.http_external_port.unwrap_or(port))
+    }
+
+    /// The port HTTPS should be advertised under externally. See
+    /// [DomainSpec::effective_http_external_port].
+    pub fn effective_https_external_port(&self) -> Option<u16> {
+        self.https_port.map(|port| self.https_external_port.unwrap_or(port))
+    }
+
+    /// Returns a copy with `http_external_port`/`https_external_port` backfilled to their
+    /// effective value (see [DomainSpec::effective_http_external_port]), so a consumer of the
+    /// published spec always sees an explicit external port instead of having to re-derive the
+    /// fallback itself.
+    pub fn with_effective_external_ports(self) -> DomainSpec {
+        let http_external_port = self.effective_http_external_port();
+        let https_external_port = self.effective_https_external_port();
+        DomainSpec {
+            http_external_port: http_external_port,
+            https_external_port: https_external_port,
+            ..self
+        }
+    }
+
+    /// The tuple equality/hashing/ordering are derived from. Built from the canonical form so
+    /// that `a == b ⇒ hash(a) == hash(b)` holds regardless of how `a` and `b` were constructed.
+    /// Returns an owned `String` rather than a `&str` into `self`, since the canonical domain
+    /// name may differ from `self.domain_name` and so can't be borrowed from it. The external
+    /// ports are folded in via their *effective* value, so a spec with an explicit external port
+    /// equal to its internal one is indistinguishable from one that omitted it.
+    fn get_identity_key(&self) -> (String, Option<u16>, Option<u16>, Option<u16>, Option<u16>) {
+        let canonical = self.canonical();
+        (canonical.domain_name,
+         canonical.http_port,
+         canonical.https_port,
+         self.effective_http_external_port(),
+         self.effective_https_external_port())
+    }
+}
+
+impl PartialEq for DomainSpec {
+    fn eq(&self, other: &DomainSpec) -> bool {
+        self.get_identity_key() == other.get_identity_key()
+    }
+}
+
+impl Eq for DomainSpec {}
+
+impl Hash for DomainSpec {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.get_identity_key().hash(state)
+    }
+}
+
+impl PartialOrd for DomainSpec {
+    fn partial_cmp(&self, other: &DomainSpec) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for DomainSpec {
+    fn cmp(&self, other: &DomainSpec) -> Ordering {
+        self.get_identity_key().cmp(&other.get_identity_key())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+    use super::{DomainSpec, Host, HostPort};
+
+    #[test]
+    fn parse_bare_domain_assumes_both_schemes() {
+        let spec = DomainSpec::parse("example.org").unwrap();
+        assert_eq!(spec.domain_name, "example.org");
+        assert_eq!(spec.http_port, Some(80));
+        assert_eq!(spec.https_port, Some(443));
+    }
+
+    #[test]
+    fn parse_https_only() {
+        let spec = DomainSpec::parse("admin.example.org:https").unwrap();
+        assert_eq!(spec.domain_name, "admin.example.org");
+        assert_eq!(spec.http_port, None);
+        assert_eq!(spec.https_port, Some(443));
+    }
+
+    #[test]
+    fn parse_both_schemes_with_custom_ports() {
+        let spec = DomainSpec::parse("app.example.org:http=8080:https=8043").unwrap();
+        assert_eq!(spec.domain_name, "app.example.org");
+        assert_eq!(spec.http_port, Some(8080));
+        assert_eq!(spec.https_port, Some(8043));
+    }
+
+    #[test]
+    fn parse_rejects_empty_input() {
+        assert!(DomainSpec::parse("").is_err());
+    }
+
+    #[test]
+    fn parse_recognizes_external_port_overrides() {
+        let spec = DomainSpec::parse("app.example.org:http=8080@80:https=8443@443").unwrap();
+        assert_eq!(spec.http_port, Some(8080));
+        assert_eq!(spec.http_external_port, Some(80));
+        assert_eq!(spec.https_port, Some(8443));
+        assert_eq!(spec.https_external_port, Some(443));
+    }
+
+    #[test]
+    fn parse_without_external_port_leaves_it_unset() {
+        let spec = DomainSpec::parse("app.example.org:http=8080").unwrap();
+        assert_eq!(spec.http_external_port, None);
+    }
+
+    #[test]
+    fn effective_external_port_falls_back_to_internal_port() {
+        let spec = DomainSpec::parse("app.example.org:http=8080").unwrap();
+        assert_eq!(spec.effective_http_external_port(), Some(8080));
+        assert_eq!(spec.effective_https_external_port(), None);
+    }
+
+    #[test]
+    fn effective_external_port_uses_override_when_given() {
+        let spec = DomainSpec::parse("app.example.org:http=8080@80").unwrap();
+        assert_eq!(spec.effective_http_external_port(), Some(80));
+    }
+
+    #[test]
+    fn with_effective_external_ports_backfills_omitted_override() {
+        let spec = DomainSpec::parse("app.example.org:http=8080").unwrap().with_effective_external_ports();
+        assert_eq!(spec.http_external_port, Some(8080));
+    }
+
+    #[test]
+    fn specs_with_an_explicit_external_port_equal_to_the_internal_one_are_equal_to_specs_without_one() {
+        let explicit = DomainSpec::parse("app.example.org:http=8080@8080").unwrap();
+        let omitted = DomainSpec::parse("app.example.org:http=8080").unwrap();
+        assert_eq!(explicit, omitted);
+    }
+
+    #[test]
+    fn specs_with_different_external_ports_are_not_equal() {
+        let a = DomainSpec::parse("app.example.org:http=8080@80").unwrap();
+        let b = DomainSpec::parse("app.example.org:http=8080@8080").unwrap();
+        assert!(a != b);
+    }
+
+    #[test]
+    fn parse_recognizes_ipv4_literal_host() {
+        let spec = DomainSpec::parse("10.0.0.1:https").unwrap();
+        assert_eq!(spec.domain_name, "10.0.0.1");
+        assert_eq!(spec.host(), Host::Ip(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))));
+    }
+
+    #[test]
+    fn parse_recognizes_bracketed_ipv6_literal_host() {
+        let spec = DomainSpec::parse("[::1]:http=8080").unwrap();
+        assert_eq!(spec.domain_name, "[::1]");
+        assert_eq!(spec.http_port, Some(8080));
+        assert_eq!(spec.host(), Host::Ip(IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1))));
+    }
+
+    #[test]
+    fn host_treats_plain_names_as_dns() {
+        let spec = DomainSpec::parse("example.org").unwrap();
+        assert_eq!(spec.host(), Host::Dns("example.org".to_owned()));
+    }
+
+    #[test]
+    fn canonical_lowercases_domain_name() {
+        let spec = DomainSpec::parse("Example.ORG").unwrap();
+        assert_eq!(spec.canonical().domain_name, "example.org");
+    }
+
+    #[test]
+    fn canonical_strips_trailing_dot() {
+        let spec = DomainSpec::parse("example.org.").unwrap();
+        assert_eq!(spec.canonical().domain_name, "example.org");
+    }
+
+    #[test]
+    fn canonical_punycode_encodes_non_ascii_labels() {
+        let spec = DomainSpec::parse("bücher.example").unwrap();
+        assert_eq!(spec.canonical().domain_name, "xn--bcher-kva.example");
+    }
+
+    #[test]
+    fn canonical_leaves_wildcard_label_alone() {
+        let spec = DomainSpec::parse("*.Example.org").unwrap();
+        assert_eq!(spec.canonical().domain_name, "*.example.org");
+    }
+
+    #[test]
+    fn canonical_normalizes_leading_dot_to_bare_name() {
+        let spec = DomainSpec::parse(".Example.org").unwrap();
+        assert_eq!(spec.canonical().domain_name, "example.org");
+    }
+
+    #[test]
+    fn canonical_leaves_ip_literals_alone() {
+        let v4 = DomainSpec::parse("10.0.0.1").unwrap();
+        assert_eq!(v4.canonical().domain_name, "10.0.0.1");
+
+        let v6 = DomainSpec::parse("[::1]").unwrap();
+        assert_eq!(v6.canonical().domain_name, "[::1]");
+    }
+
+    #[test]
+    fn specs_that_differ_only_in_case_and_trailing_dot_are_equal() {
+        let a = DomainSpec::parse("Example.org.").unwrap();
+        let b = DomainSpec::parse("example.org").unwrap();
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn specs_with_different_ports_are_not_equal() {
+        let a = DomainSpec::parse("example.org:https").unwrap();
+        let b = DomainSpec::parse("example.org").unwrap();
+        assert!(a != b);
+    }
+
+    #[test]
+    fn host_port_parses_bare_dns_name_with_no_port() {
+        let hp = HostPort::parse("example.org");
+        assert_eq!(hp.host, Host::Dns("example.org".to_owned()));
+        assert_eq!(hp.port, None);
+    }
+
+    #[test]
+    fn host_port_parses_dns_name_with_port() {
+        let hp = HostPort::parse("example.org:8080");
+        assert_eq!(hp.host, Host::Dns("example.org".to_owned()));
+        assert_eq!(hp.port, Some(8080));
+    }
+
+    #[test]
+    fn host_port_parses_ipv4_with_port() {
+        let hp = HostPort::parse("10.0.0.1:8080");
+        assert_eq!(hp.host, Host::Ip(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))));
+        assert_eq!(hp.port, Some(8080));
+    }
+
+    #[test]
+    fn host_port_parses_bracketed_ipv6_with_port() {
+        let hp = HostPort::parse("[::1]:8080");
+        assert_eq!(hp.host, Host::Ip(IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1))));
+        assert_eq!(hp.port, Some(8080));
+    }
+
+    #[test]
+    fn host_port_parses_bracketed_ipv6_with_no_port() {
+        let hp = HostPort::parse("[::1]");
+        assert_eq!(hp.host, Host::Ip(IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1))));
+        assert_eq!(hp.port, None);
+    }
+
+    #[test]
+    fn host_port_treats_unparseable_port_as_part_of_the_host() {
+        let hp = HostPort::parse("weird:name");
+        assert_eq!(hp.host, Host::Dns("weird:name".to_owned()));
+        assert_eq!(hp.port, None);
+    }
+
+    #[test]
+    fn host_port_with_and_without_a_port_are_distinct() {
+        assert!(HostPort::parse("example.org") != HostPort::parse("example.org:8080"));
+    }
+
+    #[test]
+    fn equal_specs_hash_the_same() {
+        use std::collections::HashSet;
+
+        let a = DomainSpec::parse("Example.org.").unwrap();
+        let b = DomainSpec::parse("example.org").unwrap();
+
+        let mut set = HashSet::new();
+        set.insert(a);
+        set.insert(b);
+        assert_eq!(set.len(), 1, "equal specs must collapse into a single HashSet entry");
+    }
+}
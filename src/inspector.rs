@@ -0,0 +1,127 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Christian Klauser
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::sync::Arc;
+
+use chan;
+
+use domain_spec::DomainSpec;
+
+pub mod docker;
+pub mod mock_inspector;
+
+/// Outcome of inspecting a single container: whether the configured environment variable was
+/// present, the host address publications for it should advertise, and the domain-specs parsed
+/// out of that variable.
+#[derive(Debug, Clone)]
+pub struct Inspection {
+    pub envvar_present: bool,
+    pub host: String,
+    pub specs: Vec<DomainSpec>,
+    pub health: HealthStatus,
+}
+
+/// A container's `HEALTHCHECK` status, as surfaced by the Docker daemon's inspect API
+/// (`State.Health.Status`). Containers that don't declare a healthcheck don't have a `Health`
+/// object at all, which is reported as `NoHealthcheck` rather than made up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HealthStatus {
+    NoHealthcheck,
+    Starting,
+    Healthy,
+    Unhealthy,
+}
+
+impl HealthStatus {
+    /// Whether this status is good enough to publish under `--require-healthy`: either an
+    /// explicit `healthy` result, or no healthcheck at all (nothing to gate on).
+    pub fn is_healthy(&self) -> bool {
+        match *self {
+            HealthStatus::NoHealthcheck | HealthStatus::Healthy => true,
+            HealthStatus::Starting | HealthStatus::Unhealthy => false,
+        }
+    }
+}
+
+/// A container lifecycle event as surfaced by the Docker daemon's `/events` stream (see
+/// [Inspect::watch]).
+#[derive(Debug, Clone)]
+pub struct ContainerEvent {
+    pub container_name: Arc<String>,
+    pub kind: ContainerEventKind,
+}
+
+/// The container lifecycle transitions the companion cares about. `start`/`unpause` mean "go
+/// inspect and (re-)publish"; `die`/`stop`/`destroy` mean "this container is gone, retract its
+/// publications".
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContainerEventKind {
+    Start,
+    Unpause,
+    Die,
+    Stop,
+    Destroy,
+}
+
+/// Abstracts over how container information is obtained: normally straight from the Docker
+/// daemon, faked out in tests via [mock_inspector::MockInspector]. Kept as a trait object
+/// (`Box<Inspect>`) so `companion::Context` doesn't need to know which implementation it's
+/// driving.
+pub trait Inspect {
+    fn inspect(&mut self, container_name: &Arc<String>) -> Result<Inspection, InspectionError>;
+    fn enumerate(&mut self, names: &mut Vec<String>) -> Result<(), InspectionError>;
+
+    /// Subscribes to the Docker daemon's container lifecycle events and returns a channel that
+    /// yields a [ContainerEvent] every time one of `start`/`unpause`/`die`/`stop`/`destroy`
+    /// happens. The subscription runs for the lifetime of the returned receiver; if the
+    /// underlying connection drops, the channel is closed (`recv()` returns `None`) and the
+    /// caller is expected to fall back to a full [Inspect::enumerate] pass before re-subscribing.
+    fn watch(&mut self) -> Result<chan::Receiver<ContainerEvent>, InspectionError>;
+
+    /// Returns a cheap, independent handle to the same underlying backend, for use by a
+    /// refresh worker thread. Implementations that can't sensibly be duplicated (e.g. test
+    /// doubles with pre-scripted results) return `None`, which tells the caller to fall back to
+    /// refreshing sequentially on the calling thread.
+    fn clone_handle(&self) -> Option<Box<Inspect + Send>> {
+        None
+    }
+}
+
+quick_error! {
+    #[derive(Debug)]
+    pub enum InspectionError {
+        Docker(err: ::shiplift::Error) {
+            description("Error communicating with the Docker daemon.")
+            cause(err)
+            from()
+            display(me) -> ("{} Error: {}", me.description(), err)
+        }
+        ContainerNotFound(container_name: Arc<String>) {
+            description("Container could not be found.")
+            display(me) -> ("{} name: {}", me.description(), container_name)
+        }
+        Other(err: Box<::std::error::Error + Send + Sync>) {
+            description("Inspection error.")
+            display(me) -> ("{} Error: {}", me.description(), err)
+        }
+    }
+}
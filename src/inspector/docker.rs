@@ -0,0 +1,157 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Christian Klauser
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::sync::Arc;
+use std::thread;
+
+use chan;
+use shiplift::Docker;
+use shiplift::builder::{ContainerListOptions, EventsOptions};
+
+use common::Config;
+use domain_spec::DomainSpec;
+use super::{Inspect, Inspection, InspectionError, ContainerEvent, ContainerEventKind, HealthStatus};
+
+/// [Inspect] implementation backed by a real Docker daemon, reached over the configured
+/// `docker_url` using shiplift.
+pub struct DockerInspector {
+    config: Arc<Config>,
+    docker: Docker,
+}
+
+impl DockerInspector {
+    pub fn new(config: Arc<Config>) -> DockerInspector {
+        let docker = Docker::host(config.docker_url.clone());
+        DockerInspector { config: config, docker: docker }
+    }
+
+    fn env_prefix(&self) -> String {
+        format!("{}=", self.config.envvar)
+    }
+}
+
+impl Inspect for DockerInspector {
+    fn inspect(&mut self, container_name: &Arc<String>) -> Result<Inspection, InspectionError> {
+        let container = self.docker.containers().get(container_name.as_str());
+        let details = try!(container.inspect());
+
+        let host = if self.config.docker_network {
+            details.config.hostname.clone()
+        } else {
+            details.network_settings.ip_address.clone()
+        };
+
+        let prefix = self.env_prefix();
+        let mut envvar_present = false;
+        let mut specs = Vec::new();
+        for entry in &details.config.env {
+            if !entry.starts_with(&prefix) {
+                continue;
+            }
+            envvar_present = true;
+            let value = &entry[prefix.len()..];
+            for token in value.split_whitespace() {
+                match DomainSpec::parse(token) {
+                    Ok(spec) => specs.push(spec),
+                    Err(e) => {
+                        warn!("Ignoring invalid domain-spec '{}' on container {}. Error: {}",
+                              token,
+                              container_name,
+                              e);
+                    }
+                }
+            }
+        }
+
+        let health = to_health_status(&details.state.health);
+
+        Ok(Inspection { envvar_present: envvar_present, host: host, specs: specs, health: health })
+    }
+
+    fn enumerate(&mut self, names: &mut Vec<String>) -> Result<(), InspectionError> {
+        let options = ContainerListOptions::builder().build();
+        let containers = try!(self.docker.containers().list(&options));
+        names.extend(containers.into_iter().map(|c| c.id));
+        Ok(())
+    }
+
+    fn watch(&mut self) -> Result<chan::Receiver<ContainerEvent>, InspectionError> {
+        // The `/events` stream is long-lived, so it gets its own thread; shiplift's streaming
+        // reader blocks until the connection to the daemon is closed.
+        let (send, recv) = chan::async();
+        let docker = Docker::host(self.config.docker_url.clone());
+
+        thread::spawn(move || {
+            let options = EventsOptions::builder().filter_type("container").build();
+            match docker.events(&options) {
+                Ok(events) => {
+                    for event in events {
+                        if let Some(container_event) = to_container_event(&event) {
+                            send.send(container_event);
+                        }
+                    }
+                }
+                Err(e) => {
+                    debug!("Docker event stream ended. Error: {}", e);
+                }
+            }
+            // Dropping `send` closes the channel, signalling the caller to fall back to polling.
+        });
+
+        Ok(recv)
+    }
+
+    fn clone_handle(&self) -> Option<Box<Inspect + Send>> {
+        Some(Box::new(DockerInspector::new(self.config.clone())))
+    }
+}
+
+/// Translates the `State.Health.Status` field from a container inspect response into a
+/// [HealthStatus]. The field's absence (no `HEALTHCHECK` declared), like a status string this
+/// doesn't recognize, becomes `NoHealthcheck`, which [HealthStatus::is_healthy] accepts.
+fn to_health_status(health: &Option<::shiplift::rep::Health>) -> HealthStatus {
+    match *health {
+        Some(ref health) => {
+            match health.status.as_str() {
+                "starting" => HealthStatus::Starting,
+                "healthy" => HealthStatus::Healthy,
+                "unhealthy" => HealthStatus::Unhealthy,
+                _ => HealthStatus::NoHealthcheck,
+            }
+        }
+        None => HealthStatus::NoHealthcheck,
+    }
+}
+
+/// Translates a raw shiplift event into a [ContainerEvent], ignoring event kinds the companion
+/// doesn't react to (e.g. `exec_create`, image events).
+fn to_container_event(event: &::shiplift::rep::Event) -> Option<ContainerEvent> {
+    let kind = match event.status.as_str() {
+        "start" => ContainerEventKind::Start,
+        "unpause" => ContainerEventKind::Unpause,
+        "die" => ContainerEventKind::Die,
+        "stop" => ContainerEventKind::Stop,
+        "destroy" => ContainerEventKind::Destroy,
+        _ => return None,
+    };
+    Some(ContainerEvent { container_name: Arc::new(event.id.clone()), kind: kind })
+}
@@ -0,0 +1,110 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Christian Klauser
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::error::Error;
+use std::fmt;
+
+use chan;
+
+use super::{Inspect, Inspection, InspectionError, ContainerEvent};
+
+/// Trivial [Error] used by tests to synthesize inspection failures without depending on the real
+/// Docker error types.
+#[derive(Debug)]
+pub struct FakeError;
+
+impl fmt::Display for FakeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.description())
+    }
+}
+
+impl Error for FakeError {
+    fn description(&self) -> &str {
+        "Fake inspection error"
+    }
+}
+
+impl From<FakeError> for InspectionError {
+    fn from(err: FakeError) -> InspectionError {
+        InspectionError::Other(Box::new(err))
+    }
+}
+
+/// Scriptable [Inspect] implementation used throughout the `companion` test suite: results for
+/// `inspect`/`enumerate` are configured up-front rather than coming from a real Docker daemon.
+/// Errors are stored as factories (`Box<Fn() -> InspectionError>`) rather than plain values
+/// because `InspectionError` isn't `Clone`.
+pub struct MockInspector {
+    pub inspect_results: HashMap<Arc<String>, Result<Inspection, Box<Fn() -> InspectionError>>>,
+    pub enumerate_result: Result<Vec<String>, Box<Fn() -> InspectionError>>,
+    pub watch_events: Option<chan::Receiver<ContainerEvent>>,
+    // Sender for the silent channel `watch` hands out when `watch_events` isn't set; held so that
+    // channel stays open.
+    watch_keepalive: Option<chan::Sender<ContainerEvent>>,
+}
+
+impl Default for MockInspector {
+    fn default() -> MockInspector {
+        MockInspector {
+            inspect_results: HashMap::new(),
+            enumerate_result: Ok(Vec::new()),
+            watch_events: None,
+            watch_keepalive: None,
+        }
+    }
+}
+
+impl Inspect for MockInspector {
+    fn inspect(&mut self, container_name: &Arc<String>) -> Result<Inspection, InspectionError> {
+        match self.inspect_results.get(container_name) {
+            Some(&Ok(ref inspection)) => Ok(inspection.clone()),
+            Some(&Err(ref make_error)) => Err(make_error()),
+            None => Err(InspectionError::ContainerNotFound(container_name.clone())),
+        }
+    }
+
+    fn enumerate(&mut self, names: &mut Vec<String>) -> Result<(), InspectionError> {
+        match self.enumerate_result {
+            Ok(ref found) => {
+                names.extend(found.iter().cloned());
+                Ok(())
+            }
+            Err(ref make_error) => Err(make_error()),
+        }
+    }
+
+    fn watch(&mut self) -> Result<chan::Receiver<ContainerEvent>, InspectionError> {
+        match self.watch_events.take() {
+            Some(recv) => Ok(recv),
+            // Tests that don't care about the event stream get a channel that never fires
+            // instead of an error, which would make `companion::run` fall back to polling only.
+            None => {
+                let (send, recv) = chan::async();
+                self.watch_keepalive = Some(send);
+                Ok(recv)
+            }
+        }
+    }
+}
@@ -44,6 +44,10 @@ pub mod common;
 pub mod domain_spec;
 pub mod inspector;
 pub mod publisher;
+pub mod reporter;
+pub mod acme;
+pub mod cert_store;
 pub mod companion;
+pub mod check;
 
 pub const VERSION: &'static str = env!("CARGO_PKG_VERSION");
@@ -21,8 +21,8 @@
 // SOFTWARE.
 
 use std::env;
+use std::io::Read;
 use std::sync::Arc;
-use std::rc::Rc;
 
 extern crate docopt;
 extern crate libbeachheadcompanion;
@@ -31,7 +31,11 @@ use libbeachheadcompanion::common::{stay_calm_and, stay_very_calm_and, Config,
                                     MissingContainerHandling, MissingEnvVarHandling};
 use libbeachheadcompanion::inspector;
 use libbeachheadcompanion::publisher;
+use libbeachheadcompanion::reporter::text::TextReporter;
+use libbeachheadcompanion::reporter::json::JsonReporter;
+use libbeachheadcompanion::reporter::Reporter;
 use libbeachheadcompanion::companion;
+use libbeachheadcompanion::check;
 
 extern crate rustc_serialize;
 extern crate url;
@@ -51,11 +55,13 @@ use url::Url;
 use docopt::Docopt;
 use chan_signal::Signal;
 use systemd::daemon;
+use rustc_serialize::json;
 
 #[cfg_attr(rustfmt, rustfmt_skip)]
 const USAGE: &'static str = "
 Usage: beachhead-companion [options] [--ignore-missing-envvar] [--error-missing-container] [--] <containers>...
        beachhead-companion [options] [--error-missing-envvar] --enumerate
+       beachhead-companion --check [options] [--] <containers>...
        beachhead-companion --help
        beachhead-companion --version
 
@@ -66,8 +72,22 @@ Options:
     --quiet             Only show warnings and errors.
     --no-timestamp      Don't include timestamp in log messages. Used in case timestamps get added
                         externally.
+    --log-format=FORMAT
+                        How to format log messages: `text` is the traditional human-readable line,
+                        `json` writes one JSON object per message (fields `timestamp`, `level`,
+                        `module`, `message`) for log-collection pipelines. `timestamp` is written
+                        as null when --no-timestamp is set. [default: text]
     --redis-host=HOST   Hostname or IP of the Redis server [default: localhost]
     --redis-port=PORT   Port of the Redis server [default: 6379]
+    --redis-password=PASSWORD
+                        Password to authenticate with the Redis server (Redis AUTH). Ignored if
+                        --redis-password-file is also given; leave both unset to connect without
+                        authentication.
+    --redis-password-file=PATH
+                        Path to a file containing the Redis AUTH password, so the secret doesn't
+                        end up on the command line or in the process table. Takes precedence over
+                        --redis-password if both are given.
+    --redis-tls         Connect to the Redis server over TLS instead of plaintext.
     --expire=SECONDS    Number of seconds after which to expire registration.
                         0 means no expiration. [default: 60]
     --refresh=SECONDS   Number of seconds after which to refresh registrations.
@@ -83,7 +103,47 @@ Options:
     --enumerate         Ask docker daemon for list of all running containers instead of
                         passing individual container names/ids. Enumeration will be repeated
                         on each refresh (containers can come and go)
-    --systemd           Enable systemd service manager notifications (READY, WATCHDOG).
+    --watch             Subscribe to the Docker daemon's container event stream and react to
+                        start/die/stop/destroy as they happen, instead of waiting for the next
+                        --refresh tick. --refresh keeps running alongside it as a slow
+                        safety-net re-sync, and as the fallback if the event stream isn't
+                        available or drops.
+    --require-healthy   Only publish a container's domain-specs once the Docker daemon reports
+                        its HEALTHCHECK status as healthy. A container found unhealthy is no
+                        longer published, and its registration is removed at the end of the next
+                        full refresh cycle. Containers without a declared healthcheck are
+                        unaffected.
+    --concurrency=NUM   Maximum number of containers to inspect/publish concurrently during a
+                        refresh pass. 1 disables concurrency. [default: 4]
+    --publish-max-retries=NUM
+                        Maximum number of times to retry a publish that failed with a transient
+                        error before giving up. 0 disables retrying. [default: 4]
+    --publish-base-delay-ms=MS
+                        Base delay, before jitter, for the exponential backoff between publish
+                        retries. [default: 200]
+    --publish-max-delay-ms=MS
+                        Upper bound on the backoff delay between publish retries. [default: 5000]
+    --report-format=FORMAT
+                        How to report refresh events: `text` logs them in human-readable form,
+                        `json` writes one JSON object per event to stdout for machine
+                        consumption. [default: text]
+    --acme              Auto-provision TLS certificates (via ACME http-01) for discovered domains
+                        that declare an https port. NOT YET IMPLEMENTED in this build: there is no
+                        working ACME transport, and the program refuses to start with this flag set.
+    --acme-directory-url=URL
+                        ACME directory URL to order certificates from. Required if --acme is set.
+    --acme-account-key=PATH
+                        Path to the ACME account's private key. Required if --acme is set.
+    --acme-contact-email=EMAIL
+                        Contact email to register with the ACME account. Required if --acme is set.
+    --acme-renew-within-days=DAYS
+                        Re-order a certificate once it's within this many days of expiring.
+                        [default: 30]
+    --systemd           Enable systemd service manager notifications (READY, WATCHDOG, a STATUS
+                        line with the current registration count and last refresh time). Also
+                        picks up the Redis password from the `redis-password` systemd credential
+                        (LoadCredential=) if `$CREDENTIALS_DIRECTORY` has one and
+                        --redis-password/--redis-password-file wasn't given.
     --error-missing-envvar
                         Consider `envvar` missing on a container an error. Automatically enabled
                         for containers that are listed explicitly unless --ignore-missing-envvar
@@ -98,21 +158,36 @@ Options:
                         to monitor your containers.
     -n, --dry-run       Don't update registrations, just check container status and configuration.
                         Ignores --quiet.
+    --check             Run a single check pass and exit, instead of the normal refresh loop:
+                        verifies that every listed container's registration is present in Redis
+                        and its TTL is still above --check-warn/--check-crit, then prints a
+                        one-line summary and exits with a Nagios/Icinga-compatible status code
+                        (0 OK, 1 WARNING, 2 CRITICAL, 3 UNKNOWN).
+    --check-warn=SECONDS
+                        TTL threshold, in seconds, below which --check reports WARNING.
+                        [default: 10]
+    --check-crit=SECONDS
+                        TTL threshold, in seconds, below which --check reports CRITICAL, in
+                        addition to a missing or expired registration always being CRITICAL.
+                        0 disables this extra threshold. [default: 0]
 
 The docker container with the supplied name needs to exist and have the BEACHHEAD_DOMAINS
 environment variable set (or whatever is configured).
 The environment variable lists 'domain-specs' separated by spaces. A domain-spec has the format
-'DOMAIN[:http[=PORT]][:https[=PORT]]'. If neither 'http' not 'https' is specified, both
-are assumed. Default ports are 80 for HTTP and 443 for HTTPS. Whether HTTP/2.0 is supported
-or not does not concern the beachhead. If both the 'naked' and a 'www.' domain need to be
-supported, you need to add both domains to the list.
+'DOMAIN[:http[=PORT[@EXTERNAL]]][:https[=PORT[@EXTERNAL]]]'. If neither 'http' not 'https' is
+specified, both are assumed. Default ports are 80 for HTTP and 443 for HTTPS. Whether HTTP/2.0 is
+supported or not does not concern the beachhead. If both the 'naked' and a 'www.' domain need to
+be supported, you need to add both domains to the list.
+'@EXTERNAL' lets a container advertise a different externally visible port than the one it's
+actually listening on, e.g. for a container speaking plain HTTP on 8080 that should be fronted on
+443 by another load balancer in front of the beachhead. It defaults to PORT itself when omitted.
 
 Example:
-  BEACHHEAD_DOMAINS=example.org admin.example.org:https app.example.org:http=8080:https=8043
+  BEACHHEAD_DOMAINS=example.org admin.example.org:https app.example.org:http=8080@80:https=8043
     is parsed as
   example.org with http=80, https=443
   admin.example.org with https=443
-  app.example.org with http=8080 and https=8043
+  app.example.org with http=8080 (advertised externally as 80) and https=8043
 
 One way to use beachhead-companion is to supply an explicit list of container names/ids to check
 for domain specifications. Alternatively, you can have beachhead-companion check all containers
@@ -134,6 +209,9 @@ struct Args {
     flag_quiet: bool,
     flag_redis_host: String,
     flag_redis_port: u16,
+    flag_redis_password: Option<String>,
+    flag_redis_password_file: Option<String>,
+    flag_redis_tls: bool,
     flag_expire: u32,
     flag_refresh: Option<u32>,
     flag_docker_url: Url,
@@ -142,12 +220,28 @@ struct Args {
     arg_containers: Vec<String>,
     flag_docker_network: bool,
     flag_dry_run: bool,
+    flag_check: bool,
+    flag_check_warn: u32,
+    flag_check_crit: u32,
     flag_error_missing_envvar: bool,
     flag_error_missing_container: bool,
     flag_ignore_missing_envvar: bool,
     flag_enumerate: bool,
+    flag_watch: bool,
+    flag_require_healthy: bool,
     flag_systemd: bool,
     flag_no_timestamp: bool,
+    flag_log_format: String,
+    flag_concurrency: u32,
+    flag_publish_max_retries: u32,
+    flag_publish_base_delay_ms: u64,
+    flag_publish_max_delay_ms: u64,
+    flag_report_format: String,
+    flag_acme: bool,
+    flag_acme_directory_url: Option<Url>,
+    flag_acme_account_key: Option<String>,
+    flag_acme_contact_email: Option<String>,
+    flag_acme_renew_within_days: u32,
 }
 
 // Implement Default by parsing an (almost) empty command line.
@@ -163,14 +257,26 @@ impl Default for Args {
 }
 
 impl Args {
-    fn deconstruct(self) -> (Config, Vec<String>) {
+    /// Consumes the parsed command line into a [Config], reading `--redis-password-file` (if
+    /// given) off disk so the password itself never has to round-trip through docopt/`Args`.
+    fn deconstruct(self) -> Result<(Config, Vec<String>), std::io::Error> {
+        let redis_password = match self.flag_redis_password_file {
+            Some(ref path) => {
+                let mut contents = String::new();
+                try!(std::fs::File::open(path).and_then(|mut f| f.read_to_string(&mut contents)));
+                Some(Arc::new(contents.trim().to_owned()))
+            }
+            None => self.flag_redis_password.map(Arc::new),
+        };
         let config = Config {
-            redis_host: Rc::new(self.flag_redis_host),
+            redis_host: Arc::new(self.flag_redis_host),
             redis_port: self.flag_redis_port,
-            key_prefix: Rc::new(self.flag_key_prefix),
+            redis_password: redis_password,
+            redis_tls: self.flag_redis_tls,
+            key_prefix: Arc::new(self.flag_key_prefix),
             docker_url: self.flag_docker_url,
             enumerate: self.flag_enumerate,
-            envvar: Rc::new(self.flag_envvar),
+            envvar: Arc::new(self.flag_envvar),
             dry_run: self.flag_dry_run,
             expire_seconds: if self.flag_expire == 0 {
                 None
@@ -199,10 +305,23 @@ impl Args {
             } else {
                 MissingContainerHandling::Ignore
             },
+            watch: self.flag_watch,
+            require_healthy: self.flag_require_healthy,
             systemd: self.flag_systemd,
             watchdog_microseconds: None,
+            concurrency: self.flag_concurrency,
+            publish_max_retries: self.flag_publish_max_retries,
+            publish_base_delay_ms: self.flag_publish_base_delay_ms,
+            publish_max_delay_ms: self.flag_publish_max_delay_ms,
+            acme_enabled: self.flag_acme,
+            acme_directory_url: self.flag_acme_directory_url,
+            acme_account_key_path: self.flag_acme_account_key.map(Arc::new),
+            acme_contact_email: self.flag_acme_contact_email.map(Arc::new),
+            acme_renew_within_days: self.flag_acme_renew_within_days,
+            check_warn_seconds: self.flag_check_warn,
+            check_crit_seconds: self.flag_check_crit,
         };
-        (config, self.arg_containers)
+        Ok((config, self.arg_containers))
     }
 }
 
@@ -227,23 +346,49 @@ fn args_transform(args: &mut Args) {
     }
 }
 
+fn build_reporter(format: &str) -> Arc<Reporter> {
+    match format {
+        "json" => Arc::new(JsonReporter),
+        _ => Arc::new(TextReporter),
+    }
+}
+
+/// Reads a systemd credential (see `systemd.exec(5)`'s `LoadCredential=`/`SetCredential=`) named
+/// `name` out of the directory systemd points at via `$CREDENTIALS_DIRECTORY`. Returns `Ok(None)`,
+/// rather than an error, when `$CREDENTIALS_DIRECTORY` isn't set or doesn't contain `name` at
+/// all, since most deployments aren't using this mechanism.
+fn read_systemd_credential(name: &str) -> Result<Option<String>, std::io::Error> {
+    let dir = match env::var("CREDENTIALS_DIRECTORY") {
+        Ok(dir) => dir,
+        Err(_) => return Ok(None),
+    };
+    let path = std::path::Path::new(&dir).join(name);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let mut contents = String::new();
+    try!(std::fs::File::open(path).and_then(|mut f| f.read_to_string(&mut contents)));
+    Ok(Some(contents.trim().to_owned()))
+}
+
 fn read_systemd_config(config: &mut Config) -> Result<(), std::io::Error> {
     if config.systemd {
         match daemon::watchdog_enabled(false) {
-            Ok(0) => {
-                config.watchdog_microseconds = None;
-                Ok(())
-            }
-            Ok(dog_us) => {
-                config.watchdog_microseconds = Some(dog_us);
-                Ok(())
-            }
+            Ok(0) => config.watchdog_microseconds = None,
+            Ok(dog_us) => config.watchdog_microseconds = Some(dog_us),
             // Yes, we need to re-package the Result object because the Ok-type has changed.
-            Err(e) => Err(e),
+            Err(e) => return Err(e),
+        }
+        // Let a systemd credential (LoadCredential=redis-password:...) supply the Redis password
+        // without it ever touching the command line, but don't clobber an explicit
+        // --redis-password/--redis-password-file if one was already given.
+        if config.redis_password.is_none() {
+            if let Some(password) = try!(read_systemd_credential("redis-password")) {
+                config.redis_password = Some(Arc::new(password));
+            }
         }
-    } else {
-        Ok(())
     }
+    Ok(())
 }
 
 fn main() {
@@ -253,14 +398,25 @@ fn main() {
     args_transform(&mut args);
 
     stay_calm_and(init_log(&args));
-    let (mut config, arg_containers) = args.deconstruct();
+    let reporter = build_reporter(&args.flag_report_format);
+    let check_mode = args.flag_check;
+    let (mut config, arg_containers) = stay_calm_and(args.deconstruct());
     if let Err(e) = read_systemd_config(&mut config) {
         error!("systemd support is enabled, but sd_watchdog_enabled call failed. {}", e);
         ::std::process::exit(2);
     }
     let config = Arc::new(config);
     let mut containers = Vec::with_capacity(arg_containers.len());
-    containers.extend(arg_containers.into_iter().map(|x| Rc::new(x)));
+    containers.extend(arg_containers.into_iter().map(|x| Arc::new(x)));
+
+    if check_mode {
+        let mut docker_inspector = inspector::docker::DockerInspector::new(config.clone());
+        let redis_publisher = publisher::redis::RedisPublisher::new(config.clone());
+        let (status, summary) = check::check(&config, &mut docker_inspector, &redis_publisher, &containers);
+        println!("{}", summary);
+        ::std::process::exit(status.exit_code());
+    }
+
     // Signals
     //   Interrupt is to support Ctrl+C
     //   Term is to support graceful shutdown via kill
@@ -272,10 +428,43 @@ fn main() {
     stay_very_calm_and(companion::run(config,
                                       docker_inspector,
                                       redis_publisher,
+                                      reporter,
                                       abort_signal,
                                       &containers));
 }
 
+/// One JSON-lines log record, emitted by `--log-format=json`. `timestamp` mirrors
+/// `--no-timestamp`: encoded as `"timestamp":null` (the field is always present, just empty)
+/// rather than left out, when timestamps are suppressed, so an external collector that adds its
+/// own still gets a record with a consistent, fixed set of fields.
+#[derive(RustcEncodable)]
+struct JsonLogLine {
+    timestamp: Option<String>,
+    level: String,
+    module: String,
+    message: String,
+}
+
+/// Formats a single log line as `--log-format=json` would, given the pieces `env_logger`'s
+/// format closure has on hand. Split out from [init_log] so it can be unit-tested without going
+/// through an actual `log::LogRecord`.
+fn format_json_log_line(timestamp: Option<chrono::DateTime<chrono::Local>>,
+                        level: log::LogLevel,
+                        module: &str,
+                        message: &str)
+                        -> String {
+    let line = JsonLogLine {
+        timestamp: timestamp.map(|t| t.to_rfc3339()),
+        level: level.to_string(),
+        module: module.to_owned(),
+        message: message.to_owned(),
+    };
+    match json::encode(&line) {
+        Ok(encoded) => encoded,
+        Err(e) => format!("{{\"level\":\"ERROR\",\"message\":\"failed to encode log line: {}\"}}", e),
+    }
+}
+
 /// Handles the verbosity options by initializing the logger accordingly.
 /// Can be overridden using RUST_LOG.
 fn init_log(args: &Args) -> Result<(), log::SetLoggerError> {
@@ -283,7 +472,20 @@ fn init_log(args: &Args) -> Result<(), log::SetLoggerError> {
     let mut log_builder = env_logger::LogBuilder::new();
 
     // log format
-    if args.flag_no_timestamp {
+    let no_timestamp = args.flag_no_timestamp;
+    if args.flag_log_format == "json" {
+        log_builder.format(move |record| {
+            let timestamp = if no_timestamp {
+                None
+            } else {
+                Some(chrono::Local::now())
+            };
+            format_json_log_line(timestamp,
+                                  record.level(),
+                                  record.location().module_path(),
+                                  &record.args().to_string())
+        });
+    } else if no_timestamp {
         // An external log collection system probably adds timestamps to our messages
         log_builder.format(|record| {
             format!("[{}] {}: {}", record.location().module_path(), record.level(), record.args())
@@ -316,10 +518,21 @@ fn init_log(args: &Args) -> Result<(), log::SetLoggerError> {
 
 #[cfg(test)]
 mod test {
-    use super::{USAGE, args_transform, Args};
+    use std::sync::Mutex;
+
+    use super::{USAGE, args_transform, Args, format_json_log_line, read_systemd_credential};
     use docopt;
+    use chrono;
+    use log;
     use libbeachheadcompanion::common;
 
+    lazy_static! {
+        // `read_systemd_credential` tests mutate the process-global `CREDENTIALS_DIRECTORY` env
+        // var; guard them with this so they can't race each other under the parallel test
+        // harness (threads would otherwise see each other's half-set-up/torn-down state).
+        static ref CREDENTIALS_DIRECTORY_GUARD: Mutex<()> = Mutex::new(());
+    }
+
     #[test]
     fn docopt_spec() {
         docopt::Docopt::new(USAGE).unwrap();
@@ -422,10 +635,122 @@ mod test {
         let args_expire = args.flag_expire;
 
         // #### WHEN  ####
-        let (config, _) = args.deconstruct();
+        let (config, _) = args.deconstruct().expect("deconstruct must succeed without a password file");
 
         // #### THEN  ####
         assert_eq!(config.expire_seconds, Some(args_expire));
     }
 
+    #[test]
+    fn redis_password_file_is_read_and_trimmed() {
+        common::init_log();
+        // #### GIVEN ####
+        let mut file = ::std::env::temp_dir();
+        file.push("beachhead-companion-test-redis-password");
+        {
+            use std::io::Write;
+            let mut f = ::std::fs::File::create(&file).unwrap();
+            f.write_all(b"s3cret\n").unwrap();
+        }
+        let mut args: Args = Default::default();
+        args.flag_redis_password_file = Some(file.to_str().unwrap().to_owned());
+
+        // #### WHEN  ####
+        let (config, _) = args.deconstruct().expect("password file must be readable");
+        ::std::fs::remove_file(&file).unwrap();
+
+        // #### THEN  ####
+        assert_eq!(config.redis_password.map(|p| (*p).clone()), Some("s3cret".to_owned()));
+    }
+
+    #[test]
+    fn redis_password_file_missing_surfaces_error() {
+        common::init_log();
+        // #### GIVEN ####
+        let mut args: Args = Default::default();
+        args.flag_redis_password_file = Some("/nonexistent/beachhead-companion-test".to_owned());
+
+        // #### WHEN  ####
+        let result = args.deconstruct();
+
+        // #### THEN  ####
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn systemd_credential_is_read_and_trimmed_when_present() {
+        common::init_log();
+        let _guard = CREDENTIALS_DIRECTORY_GUARD.lock().unwrap_or_else(|e| e.into_inner());
+        // #### GIVEN ####
+        let mut dir = ::std::env::temp_dir();
+        dir.push("beachhead-companion-test-credentials-present");
+        ::std::fs::create_dir_all(&dir).unwrap();
+        {
+            use std::io::Write;
+            let mut f = ::std::fs::File::create(dir.join("redis-password")).unwrap();
+            f.write_all(b"cr3d3ntial\n").unwrap();
+        }
+        ::std::env::set_var("CREDENTIALS_DIRECTORY", dir.to_str().unwrap());
+
+        // #### WHEN  ####
+        let credential = read_systemd_credential("redis-password");
+
+        // #### THEN  ####
+        ::std::env::remove_var("CREDENTIALS_DIRECTORY");
+        ::std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(credential.unwrap(), Some("cr3d3ntial".to_owned()));
+    }
+
+    #[test]
+    fn systemd_credential_is_none_when_not_configured() {
+        common::init_log();
+        let _guard = CREDENTIALS_DIRECTORY_GUARD.lock().unwrap_or_else(|e| e.into_inner());
+        // #### GIVEN ####
+        ::std::env::remove_var("CREDENTIALS_DIRECTORY");
+
+        // #### WHEN  ####
+        let credential = read_systemd_credential("redis-password");
+
+        // #### THEN  ####
+        assert_eq!(credential.unwrap(), None);
+    }
+
+    #[test]
+    fn json_log_line_includes_timestamp_by_default() {
+        common::init_log();
+        // #### GIVEN ####
+        let now = chrono::Local::now();
+
+        // #### WHEN  ####
+        let line = format_json_log_line(Some(now), log::LogLevel::Info, "beachhead_companion", "hello");
+
+        // #### THEN ####
+        assert!(line.contains("\"timestamp\":\""), "line: {}", line);
+        assert!(line.contains("\"level\":\"INFO\""), "line: {}", line);
+        assert!(line.contains("\"module\":\"beachhead_companion\""), "line: {}", line);
+        assert!(line.contains("\"message\":\"hello\""), "line: {}", line);
+    }
+
+    #[test]
+    fn json_log_line_encodes_timestamp_as_null_when_none() {
+        common::init_log();
+        // #### WHEN  ####
+        let line = format_json_log_line(None, log::LogLevel::Warn, "beachhead_companion", "careful");
+
+        // #### THEN ####
+        assert!(line.contains("\"timestamp\":null"), "line: {}", line);
+    }
+
+    #[test]
+    fn json_log_line_escapes_special_characters() {
+        common::init_log();
+        // #### WHEN  ####
+        let line = format_json_log_line(None,
+                                        log::LogLevel::Error,
+                                        "beachhead_companion",
+                                        "quote \" and newline \n");
+
+        // #### THEN ####
+        assert!(line.contains("quote \\\" and newline \\n"), "line: {}", line);
+    }
 }
@@ -0,0 +1,96 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Christian Klauser
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use domain_spec::DomainSpec;
+
+pub mod redis;
+pub mod mock_publisher;
+
+/// A host's resolved set of domain-specs, ready to be written to the publishing backend.
+#[derive(Debug, Clone)]
+pub struct Publication {
+    pub host: String,
+    pub specs: Vec<DomainSpec>,
+}
+
+/// Abstracts over where published domain-specs end up: normally Redis, faked out in tests via
+/// [mock_publisher::MockPublisher].
+pub trait Publish {
+    fn publish(&mut self, publication: &Publication) -> Result<(), PublishingError>;
+
+    /// Removes a previously published host's domain-specs from the backend. Used by
+    /// reconciliation (see `::companion::Context::reconcile`) to prune publications for
+    /// containers that have disappeared since the last refresh cycle.
+    fn unpublish(&mut self, host: &str, specs: &[DomainSpec]) -> Result<(), PublishingError>;
+
+    /// Returns a cheap, independent handle to the same underlying backend, for use by a
+    /// refresh worker thread. Implementations that can't sensibly be duplicated (e.g. test
+    /// doubles that record publications for later inspection) return `None`, which tells the
+    /// caller to fall back to publishing sequentially on the calling thread.
+    fn clone_handle(&self) -> Option<Box<Publish + Send>> {
+        None
+    }
+}
+
+/// Reads back whether (and for how long) a host's registration is still live in the publishing
+/// backend. Kept separate from [Publish] because `--check` mode (see `::check`) only ever reads,
+/// and doesn't want `Publish`'s `&mut self`/worker-pool-cloning contract.
+pub trait CheckBackend {
+    /// `None` if `host` has no registration at all; `Some(ttl_seconds)` otherwise, where a
+    /// negative `ttl_seconds` means the registration was stored without an expiration.
+    fn check_ttl(&self, host: &str) -> Result<Option<i64>, PublishingError>;
+}
+
+quick_error! {
+    #[derive(Debug)]
+    pub enum PublishingError {
+        Redis(err: ::redis::RedisError) {
+            description("Error communicating with Redis.")
+            cause(err)
+            from()
+            display(me) -> ("{} Error: {}", me.description(), err)
+        }
+        Encoding(err: ::rustc_serialize::json::EncoderError) {
+            description("Error encoding domain-specs as JSON.")
+            cause(err)
+            from()
+            display(me) -> ("{} Error: {}", me.description(), err)
+        }
+        Other(err: Box<::std::error::Error + Send + Sync>) {
+            description("Publishing error.")
+            display(me) -> ("{} Error: {}", me.description(), err)
+        }
+    }
+}
+
+impl PublishingError {
+    /// Whether this failure likely reflects a transient condition (e.g. a dropped connection)
+    /// that's worth retrying, as opposed to a permanent one (e.g. malformed specs) that will just
+    /// fail again. Used by the refresh loop to decide whether to burn a retry on this error.
+    pub fn is_transient(&self) -> bool {
+        match *self {
+            PublishingError::Redis(_) => true,
+            PublishingError::Encoding(_) |
+            PublishingError::Other(_) => false,
+        }
+    }
+}
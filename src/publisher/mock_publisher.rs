@@ -0,0 +1,114 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Christian Klauser
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+
+use domain_spec::DomainSpec;
+use super::{Publish, Publication, PublishingError, CheckBackend};
+
+/// Trivial [Error] used by tests to synthesize publishing failures without depending on a real
+/// Redis connection.
+#[derive(Debug)]
+pub struct MockError;
+
+impl fmt::Display for MockError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.description())
+    }
+}
+
+impl Error for MockError {
+    fn description(&self) -> &str {
+        "Fake publishing error"
+    }
+}
+
+impl From<MockError> for PublishingError {
+    fn from(err: MockError) -> PublishingError {
+        PublishingError::Other(Box::new(err))
+    }
+}
+
+/// Scriptable [Publish] implementation used by the `companion` test suite. When `error_trigger`
+/// is set, any publication whose host or specs contain the given substring is failed instead of
+/// recorded, which is how the tests exercise the companion's error handling without a real Redis
+/// instance.
+#[derive(Default)]
+pub struct MockPublisher {
+    pub publications: Vec<Publication>,
+    pub unpublications: Vec<(String, Vec<DomainSpec>)>,
+    pub error_trigger: Option<(String, Box<Fn() -> PublishingError>)>,
+}
+
+impl Publish for MockPublisher {
+    fn publish(&mut self, publication: &Publication) -> Result<(), PublishingError> {
+        if let Some((ref pattern, ref make_error)) = self.error_trigger {
+            let matches = publication.host.contains(pattern.as_str()) ||
+                          publication.specs.iter().any(|s| s.domain_name.contains(pattern.as_str()));
+            if matches {
+                return Err(make_error());
+            }
+        }
+        self.publications.push(publication.clone());
+        Ok(())
+    }
+
+    fn unpublish(&mut self, host: &str, specs: &[DomainSpec]) -> Result<(), PublishingError> {
+        self.unpublications.push((host.to_owned(), specs.to_owned()));
+        Ok(())
+    }
+}
+
+// `companion`'s tests share a `MockPublisher` between the context under test and their own
+// assertions by wrapping it in `Arc<RefCell<_>>`; this lets that wrapper stand in for `Box<Publish>`
+// directly.
+impl Publish for Arc<RefCell<MockPublisher>> {
+    fn publish(&mut self, publication: &Publication) -> Result<(), PublishingError> {
+        self.borrow_mut().publish(publication)
+    }
+
+    fn unpublish(&mut self, host: &str, specs: &[DomainSpec]) -> Result<(), PublishingError> {
+        self.borrow_mut().unpublish(host, specs)
+    }
+}
+
+/// Scriptable [CheckBackend] implementation used by the `check` test suite. Results (or error
+/// factories, since `PublishingError` isn't `Clone`) are keyed by host, mirroring
+/// `MockInspector::inspect_results`.
+#[derive(Default)]
+pub struct MockCheckBackend {
+    pub ttls: HashMap<String, Result<Option<i64>, Box<Fn() -> PublishingError>>>,
+}
+
+impl CheckBackend for MockCheckBackend {
+    fn check_ttl(&self, host: &str) -> Result<Option<i64>, PublishingError> {
+        match self.ttls.get(host) {
+            Some(&Ok(ttl)) => Ok(ttl),
+            Some(&Err(ref make_error)) => Err(make_error()),
+            None => Ok(None),
+        }
+    }
+}
@@ -0,0 +1,95 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Christian Klauser
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::sync::Arc;
+
+use redis::{Client, Commands};
+use rustc_serialize::json;
+use url::Url;
+
+use common::Config;
+use domain_spec::DomainSpec;
+use super::{Publish, Publication, PublishingError, CheckBackend};
+
+/// [Publish] implementation that stores each host's domain-specs as a JSON-encoded string under
+/// `key_prefix + host`, expiring the key after `expire_seconds` so that a companion instance that
+/// stops refreshing eventually disappears from Redis on its own.
+pub struct RedisPublisher {
+    config: Arc<Config>,
+    client: Client,
+}
+
+impl RedisPublisher {
+    pub fn new(config: Arc<Config>) -> RedisPublisher {
+        let scheme = if config.redis_tls { "rediss" } else { "redis" };
+        // Built via `Url`'s own setters, rather than interpolating the password into the string
+        // directly, so that base64/punctuation-heavy AUTH passwords (which routinely contain `/`,
+        // `+` or `=`) get percent-encoded instead of corrupting the authority/path parse.
+        let mut url = Url::parse(&format!("{}://{}:{}/", scheme, config.redis_host, config.redis_port))
+            .expect("redis_host/redis_port must form a valid URL");
+        if let Some(ref password) = config.redis_password {
+            url.set_password(Some(password))
+                .expect("redis_password must be settable on a valid redis:// URL");
+        }
+        let client = Client::open(url.as_str())
+            .expect("redis_host/redis_port/redis_password must form a valid URL");
+        RedisPublisher { config: config, client: client }
+    }
+
+    fn key_for(&self, host: &str) -> String {
+        format!("{}{}", self.config.key_prefix, host)
+    }
+}
+
+impl Publish for RedisPublisher {
+    fn publish(&mut self, publication: &Publication) -> Result<(), PublishingError> {
+        let connection = try!(self.client.get_connection());
+        let key = self.key_for(&publication.host);
+        let encoded = try!(json::encode(&publication.specs));
+        let _: () = try!(connection.set(&key, encoded));
+        if let Some(expire_seconds) = self.config.expire_seconds {
+            let _: () = try!(connection.expire(&key, expire_seconds as usize));
+        }
+        Ok(())
+    }
+
+    fn unpublish(&mut self, host: &str, _specs: &[DomainSpec]) -> Result<(), PublishingError> {
+        let connection = try!(self.client.get_connection());
+        let key = self.key_for(host);
+        let _: () = try!(connection.del(&key));
+        Ok(())
+    }
+
+    fn clone_handle(&self) -> Option<Box<Publish + Send>> {
+        Some(Box::new(RedisPublisher::new(self.config.clone())))
+    }
+}
+
+impl CheckBackend for RedisPublisher {
+    fn check_ttl(&self, host: &str) -> Result<Option<i64>, PublishingError> {
+        let connection = try!(self.client.get_connection());
+        let key = self.key_for(host);
+        let ttl: i64 = try!(connection.ttl(&key));
+        // Redis reports a missing key as a TTL of -2; a key with no expiration at all as -1.
+        Ok(if ttl == -2 { None } else { Some(ttl) })
+    }
+}
@@ -0,0 +1,59 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Christian Klauser
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::sync::Arc;
+
+use domain_spec::DomainSpec;
+
+pub mod text;
+pub mod json;
+
+/// A single noteworthy occurrence during a refresh pass, surfaced to a [Reporter] instead of
+/// going straight to the log. Lets operators consume the companion's lifecycle in a structured,
+/// scriptable form instead of scraping log lines.
+#[derive(Debug, Clone)]
+pub enum Event {
+    /// A full enumeration finished; how many containers came from each source.
+    Enumerated { explicit: usize, implicit: usize },
+    /// About to inspect this container.
+    Inspecting { container: Arc<String> },
+    /// `host`'s domain-specs were published. Not reported in dry-run mode.
+    Published { host: String, specs: Vec<DomainSpec> },
+    /// `container` was skipped without it being considered an error (according to configuration).
+    Skipped { container: Arc<String>, reason: String },
+    /// Refreshing `container` failed and the failure is considered an error (according to
+    /// configuration); `error` is the error's `Display` rendering.
+    Failed { container: Arc<String>, error: String },
+    /// `host`'s domain-specs, published by this companion earlier, were removed: either because
+    /// `host` no longer showed up in a full refresh cycle, or because its container died, stopped
+    /// or was destroyed (see `--watch`).
+    Unpublished { host: String, specs: Vec<DomainSpec> },
+}
+
+/// Abstracts over how [Event]s are surfaced to the operator: normally pretty-printed to the log
+/// (see [text::TextReporter]), or as JSON-lines on stdout for machine consumption (see
+/// [json::JsonReporter]). Reporters are stateless, so a single instance is shared (via `Arc`)
+/// across the refresh worker pool instead of needing a per-worker handle like [::inspector::Inspect]
+/// or [::publisher::Publish].
+pub trait Reporter: Send + Sync {
+    fn report(&self, event: Event);
+}
@@ -0,0 +1,105 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Christian Klauser
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use rustc_serialize::json;
+
+use domain_spec::DomainSpec;
+use super::{Reporter, Event};
+
+// One struct per event kind (rather than deriving Encodable on `Event` itself) so that each
+// line carries a `event` discriminator key and `Arc<String>` container names can be encoded as
+// plain strings without needing an `Encodable` impl for `Arc`.
+
+#[derive(RustcEncodable)]
+struct EnumeratedLine {
+    event: &'static str,
+    explicit: usize,
+    implicit: usize,
+}
+
+#[derive(RustcEncodable)]
+struct InspectingLine {
+    event: &'static str,
+    container: String,
+}
+
+#[derive(RustcEncodable)]
+struct PublishedLine {
+    event: &'static str,
+    host: String,
+    specs: Vec<DomainSpec>,
+}
+
+#[derive(RustcEncodable)]
+struct SkippedLine {
+    event: &'static str,
+    container: String,
+    reason: String,
+}
+
+#[derive(RustcEncodable)]
+struct FailedLine {
+    event: &'static str,
+    container: String,
+    error: String,
+}
+
+#[derive(RustcEncodable)]
+struct UnpublishedLine {
+    event: &'static str,
+    host: String,
+    specs: Vec<DomainSpec>,
+}
+
+/// [Reporter] that writes one JSON object per event to stdout (JSON-lines), so operators can
+/// pipe the companion's output into log aggregation or script against it (e.g. wait for a
+/// specific domain to come online), selected via `--report-format=json`.
+pub struct JsonReporter;
+
+impl Reporter for JsonReporter {
+    fn report(&self, event: Event) {
+        let encoded = match event {
+            Event::Enumerated { explicit, implicit } => {
+                json::encode(&EnumeratedLine { event: "enumerated", explicit: explicit, implicit: implicit })
+            }
+            Event::Inspecting { container } => {
+                json::encode(&InspectingLine { event: "inspecting", container: (*container).clone() })
+            }
+            Event::Published { host, specs } => {
+                json::encode(&PublishedLine { event: "published", host: host, specs: specs })
+            }
+            Event::Skipped { container, reason } => {
+                json::encode(&SkippedLine { event: "skipped", container: (*container).clone(), reason: reason })
+            }
+            Event::Failed { container, error } => {
+                json::encode(&FailedLine { event: "failed", container: (*container).clone(), error: error })
+            }
+            Event::Unpublished { host, specs } => {
+                json::encode(&UnpublishedLine { event: "unpublished", host: host, specs: specs })
+            }
+        };
+        match encoded {
+            Ok(line) => println!("{}", line),
+            Err(e) => error!("Failed to encode report event as JSON. Error: {}", e),
+        }
+    }
+}
@@ -0,0 +1,52 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Christian Klauser
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use super::{Reporter, Event};
+
+/// Default [Reporter]: renders each event as a human-readable log message, mirroring the
+/// companion's original ad-hoc logging.
+pub struct TextReporter;
+
+impl Reporter for TextReporter {
+    fn report(&self, event: Event) {
+        match event {
+            Event::Enumerated { explicit, implicit } => {
+                debug!("Enumerated {} explicit and {} implicit container(s).", explicit, implicit);
+            }
+            Event::Inspecting { container } => {
+                debug!("Inspect {}", container);
+            }
+            Event::Published { host, specs } => {
+                info!("Updated configuration for host {}. Published {:?}", host, specs);
+            }
+            Event::Skipped { container, reason } => {
+                info!("Skipping {}. Reason: {}", container, reason);
+            }
+            Event::Failed { container, error } => {
+                error!("Failed to refresh {}. Error: {}", container, error);
+            }
+            Event::Unpublished { host, specs } => {
+                info!("Removed stale configuration for host {}. Unpublished {:?}", host, specs);
+            }
+        }
+    }
+}